@@ -0,0 +1,73 @@
+use chess::*;
+
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::engine::evaluator::{Classical, Evaluator};
+use crate::engine::searcher::{SearchConfig, Searcher};
+use crate::engine::stats::SearchStats;
+use crate::engine::tt::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::uci::{format_best_move, format_score};
+
+/// Depth the bench searches to when none is given.
+pub const DEFAULT_DEPTH: i8 = 6;
+
+// openings, middlegames and endgames, so a change that only helps one kind of position doesn't look better than it is
+const POSITIONS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP1B1PPP/R2QKB1R w KQ - 0 8",
+    "r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/8/4K3/4P3/8/8 w - - 0 1",
+];
+
+/// Searches every bench position to `depth` with a table of its own and writes what the search did to `output`,
+/// followed by the totals. Returns the totals.
+pub fn run<W: Write>(depth: i8, output: &mut W) -> SearchStats {
+    run_with(depth, &Classical::default(), output)
+}
+
+/// `run` evaluating with `evaluator`, running it with two evaluators compares them in the same search.
+pub fn run_with<E: Evaluator, W: Write>(depth: i8, evaluator: &E, output: &mut W) -> SearchStats {
+    let mut total = SearchStats::default();
+    for fen in POSITIONS.iter() {
+        let board = Board::from_str(fen).unwrap();
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        let result = Searcher::with_evaluator(SearchConfig::full(depth), &table, evaluator.clone()).search(&board, |_| ());
+        writeln!(output, "position {}", fen).unwrap();
+        writeln!(output, "bestmove {} score {}", format_best_move(result.best_move), format_score(result.score)).unwrap();
+        for iteration in &result.stats.iterations {
+            writeln!(output, "{}", iteration).unwrap();
+        }
+        writeln!(output, "{}\n", result.stats).unwrap();
+        total.merge(&result.stats);
+        total.time += result.stats.time;
+    }
+    writeln!(output, "total\n{}", total).unwrap();
+    output.flush().unwrap();
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::nnue::{Network, Nnue};
+
+    #[test]
+    fn test_bench() {
+        let mut output = vec![];
+        let total = run(2, &mut output);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("position ").count(), POSITIONS.len());
+        assert_eq!(output.matches("depth 2 nodes ").count(), POSITIONS.len());
+        assert!(output.contains(&format!("total\nnodes {} ", total.nodes)));
+        // the same search finds the same nodes every time
+        assert_eq!(run(2, &mut std::io::sink()).nodes, total.nodes);
+        // another evaluation searches the same positions, just not the same way
+        let mut output = vec![];
+        let nnue = run_with(2, &Nnue::new(Network::tiny()), &mut output);
+        assert_eq!(String::from_utf8(output).unwrap().matches("depth 2 nodes ").count(), POSITIONS.len());
+        assert_ne!(nnue.nodes, total.nodes);
+    }
+}
@@ -0,0 +1,548 @@
+use chess::*;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use super::params::EvalParams;
+use super::pawns::{pawn_structure, ranks_ahead, relative_rank, side_structure};
+
+// the light squares, b1, d1, ..., a2, c2, ...
+const LIGHT_SQUARES: BitBoard = BitBoard(0x55aa_55aa_55aa_55aa);
+
+/// A middlegame and an endgame score. They are only blended by the game phase once every term is added up.
+/// Written to files as `[mg, eg]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(from = "[i32; 2]", into = "[i32; 2]")]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+pub const fn score(mg: i32, eg: i32) -> Score {
+    Score { mg, eg }
+}
+
+impl From<[i32; 2]> for Score {
+    fn from([mg, eg]: [i32; 2]) -> Score {
+        score(mg, eg)
+    }
+}
+
+impl From<Score> for [i32; 2] {
+    fn from(s: Score) -> [i32; 2] {
+        [s.mg, s.eg]
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        score(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        score(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        score(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, n: i32) -> Score {
+        score(self.mg * n, self.eg * n)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+// piece-square score of a single piece, from its own side's point of view
+fn piece_square(piece: Piece, color: Color, sq: Square, params: &EvalParams) -> Score {
+    // the tables start with the eighth rank, black's are mirrored
+    let rank = match color {
+        Color::White => 7 - sq.get_rank().to_index(),
+        Color::Black => sq.get_rank().to_index(),
+    };
+    params.pst[piece.to_index()][rank][sq.get_file().to_index()]
+}
+
+// piece-square scores of all pieces of one side
+fn piece_squares(board: &Board, color: Color, params: &EvalParams) -> Score {
+    board.color_combined(color).fold(Score::default(), |sum, sq| sum + piece_square(board.piece_on(sq).unwrap(), color, sq, params))
+}
+
+/// Piece-square scores of a position, white's minus black's, kept apart for the middlegame and
+/// the endgame together with the game phase they are blended by.
+///
+/// Both only depend on where the pieces are, so a move changes them by a few table lookups.
+/// The search updates it with `make_move` instead of computing it from scratch in every node,
+/// always with the weights it was computed with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Psqt {
+    pub mg: i32,
+    pub eg: i32,
+    /// `EvalParams::max_phase` with all pieces on the board, may go above it after promotions
+    pub phase: i32,
+}
+
+impl Psqt {
+    pub fn new(board: &Board, params: &EvalParams) -> Self {
+        let sum = piece_squares(board, Color::White, params) - piece_squares(board, Color::Black, params);
+        let phase = ALL_PIECES.iter().map(|&p| params.phase[p.to_index()] * board.pieces(p).popcnt() as i32).sum();
+        Psqt { mg: sum.mg, eg: sum.eg, phase }
+    }
+
+    fn add(&mut self, piece: Piece, color: Color, sq: Square, params: &EvalParams) {
+        let sign = if color == Color::White { 1 } else { -1 };
+        let pst = piece_square(piece, color, sq, params);
+        self.mg += sign * pst.mg;
+        self.eg += sign * pst.eg;
+        self.phase += params.phase[piece.to_index()];
+    }
+
+    fn remove(&mut self, piece: Piece, color: Color, sq: Square, params: &EvalParams) {
+        let mut removed = Psqt::default();
+        removed.add(piece, color, sq, params);
+        self.mg -= removed.mg;
+        self.eg -= removed.eg;
+        self.phase -= removed.phase;
+    }
+
+    /// The scores after `m` is played on `board`, `params` have to be the ones the scores were computed with.
+    pub fn make_move(&self, board: &Board, m: ChessMove, params: &EvalParams) -> Self {
+        let mut psqt = *self;
+        move_changes(board, m, |piece, color, sq, added| {
+            if added {
+                psqt.add(piece, color, sq, params)
+            } else {
+                psqt.remove(piece, color, sq, params)
+            }
+        });
+        psqt
+    }
+}
+
+/// Calls `change` for every piece `m` takes off a square of `board`, with `false`, and every piece it puts on one,
+/// with `true`. Captures, en passant, promotions and the rook of a castling move are all taken care of,
+/// so an evaluation that is a sum over the pieces can follow a move without looking at the whole board.
+pub fn move_changes<F: FnMut(Piece, Color, Square, bool)>(board: &Board, m: ChessMove, mut change: F) {
+    let (from, to) = (m.get_source(), m.get_dest());
+    let piece = board.piece_on(from).unwrap();
+    let color = board.side_to_move();
+    if let Some(captured) = board.piece_on(to) {
+        change(captured, !color, to, false);
+    } else if piece == Piece::Pawn && from.get_file() != to.get_file() {
+        // en passant, the captured pawn is next to the square the capturing pawn moves from
+        change(Piece::Pawn, !color, Square::make_square(from.get_rank(), to.get_file()), false);
+    }
+    change(piece, color, from, false);
+    change(m.get_promotion().unwrap_or(piece), color, to, true);
+    if piece == Piece::King && (from.get_file().to_index() as i32 - to.get_file().to_index() as i32).abs() == 2 {
+        // castling, the rook jumps over the king
+        let (rook_from, rook_to) = if to.get_file() == File::G { (File::H, File::F) } else { (File::A, File::D) };
+        change(Piece::Rook, color, Square::make_square(from.get_rank(), rook_from), false);
+        change(Piece::Rook, color, Square::make_square(from.get_rank(), rook_to), true);
+    }
+}
+
+// blends a middlegame and an endgame score, all middlegame at max_phase and all endgame at 0
+fn taper(mg: i32, eg: i32, phase: i32, max_phase: i32) -> i32 {
+    // weights that leave every piece out of the phase only have an endgame
+    if max_phase <= 0 {
+        return eg;
+    }
+    let phase = phase.clamp(0, max_phase);
+    (mg * phase + eg * (max_phase - phase)) / max_phase
+}
+
+// squares a knight, bishop, rook or queen on sq attacks
+fn attacks(piece: Piece, sq: Square, occupied: BitBoard) -> BitBoard {
+    match piece {
+        Piece::Knight => get_knight_moves(sq),
+        Piece::Bishop => get_bishop_moves(sq, occupied),
+        Piece::Rook => get_rook_moves(sq, occupied),
+        Piece::Queen => get_bishop_moves(sq, occupied) | get_rook_moves(sq, occupied),
+        _ => EMPTY,
+    }
+}
+
+const MINOR_AND_MAJOR: [Piece; 4] = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+
+fn pieces(board: &Board, piece: Piece, color: Color) -> BitBoard {
+    board.pieces(piece) & board.color_combined(color)
+}
+
+fn material(board: &Board, color: Color, params: &EvalParams) -> Score {
+    // the king is left out, both sides always have one
+    ALL_PIECES[..5].iter().fold(Score::default(), |sum, &p| sum + params.material[p.to_index()] * pieces(board, p, color).popcnt() as i32)
+}
+
+fn bishop_pair(board: &Board, color: Color, params: &EvalParams) -> Score {
+    if pieces(board, Piece::Bishop, color).popcnt() >= 2 {
+        params.bishop_pair
+    } else {
+        Score::default()
+    }
+}
+
+// the two ranks in front of a king on rank, as far as they are on the board
+fn shield_ranks(color: Color, rank: Rank) -> BitBoard {
+    let rank = rank.to_index() as i32;
+    let ranks = match color {
+        Color::White => [rank + 1, rank + 2],
+        Color::Black => [rank - 1, rank - 2],
+    };
+    ranks.iter().filter(|r| (0..8).contains(*r)).fold(EMPTY, |b, &r| b | get_rank(Rank::from_index(r as usize)))
+}
+
+fn king_safety(board: &Board, color: Color, params: &EvalParams) -> Score {
+    let king = board.king_square(color);
+    let own_pawns = pieces(board, Piece::Pawn, color);
+    let file = king.get_file().to_index();
+    let files = get_file(king.get_file()) | get_adjacent_files(king.get_file());
+    let mut total = params.pawn_shield * (own_pawns & files & shield_ranks(color, king.get_rank())).popcnt() as i32;
+    for f in file.saturating_sub(1)..=(file + 1).min(7) {
+        if own_pawns & get_file(File::from_index(f)) == EMPTY {
+            total += params.king_open_file;
+        }
+    }
+    let zone = get_king_moves(king) | BitBoard::from_square(king);
+    let occupied = *board.combined();
+    for &piece in MINOR_AND_MAJOR.iter() {
+        for sq in pieces(board, piece, !color) {
+            total += params.king_zone_attack * (attacks(piece, sq, occupied) & zone).popcnt() as i32;
+        }
+    }
+    total
+}
+
+fn mobility(board: &Board, color: Color, params: &EvalParams) -> Score {
+    // squares guarded by an enemy pawn don't count, a piece moving there is lost for a pawn
+    let guarded = pieces(board, Piece::Pawn, !color).fold(EMPTY, |b, sq| b | get_pawn_attacks(sq, !color, !EMPTY));
+    let available = !board.color_combined(color) & !guarded;
+    let occupied = *board.combined();
+    let mut total = Score::default();
+    for (i, &piece) in MINOR_AND_MAJOR.iter().enumerate() {
+        for sq in pieces(board, piece, color) {
+            total += params.mobility[i] * (attacks(piece, sq, occupied) & available).popcnt() as i32;
+        }
+    }
+    total
+}
+
+fn rooks(board: &Board, color: Color, params: &EvalParams) -> Score {
+    let pawns = board.pieces(Piece::Pawn);
+    let own_pawns = pieces(board, Piece::Pawn, color);
+    let mut total = Score::default();
+    for sq in pieces(board, Piece::Rook, color) {
+        let file = get_file(sq.get_file());
+        if pawns & file == EMPTY {
+            total += params.rook_open_file;
+        } else if own_pawns & file == EMPTY {
+            total += params.rook_semi_open_file;
+        }
+        if relative_rank(color, sq) == 6 {
+            total += params.rook_seventh;
+        }
+    }
+    total
+}
+
+fn outposts(board: &Board, color: Color, params: &EvalParams) -> Score {
+    let own_pawns = pieces(board, Piece::Pawn, color);
+    let enemy_pawns = pieces(board, Piece::Pawn, !color);
+    let mut total = Score::default();
+    for sq in pieces(board, Piece::Knight, color) {
+        let defended = get_pawn_attacks(sq, !color, own_pawns) != EMPTY;
+        let attackable = enemy_pawns & get_adjacent_files(sq.get_file()) & ranks_ahead(color, sq.get_rank()) != EMPTY;
+        if (3..=5).contains(&relative_rank(color, sq)) && defended && !attackable {
+            total += params.knight_outpost;
+        }
+    }
+    total
+}
+
+// a bishop is hemmed in by its own pawns standing on squares of its colour
+fn bad_bishops(board: &Board, color: Color, params: &EvalParams) -> Score {
+    let own_pawns = pieces(board, Piece::Pawn, color);
+    let mut total = Score::default();
+    for sq in pieces(board, Piece::Bishop, color) {
+        let squares = if LIGHT_SQUARES & BitBoard::from_square(sq) != EMPTY { LIGHT_SQUARES } else { !LIGHT_SQUARES };
+        total += params.bad_bishop * (own_pawns & squares).popcnt() as i32;
+    }
+    total
+}
+
+/// What the pieces of one side contribute to the evaluation, term by term.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SideTerms {
+    pub material: Score,
+    /// piece-square tables
+    pub pst: Score,
+    pub bishop_pair: Score,
+    pub pawns: Score,
+    pub king_safety: Score,
+    pub mobility: Score,
+    /// rooks on open and semi-open files and on the seventh rank
+    pub rooks: Score,
+    pub outposts: Score,
+    pub bad_bishops: Score,
+}
+
+impl SideTerms {
+    // the terms computed anew in every evaluation. The search keeps piece-square tables and pawns
+    // up to date itself, they are left at 0
+    fn positional(board: &Board, color: Color, params: &EvalParams) -> Self {
+        SideTerms {
+            material: material(board, color, params),
+            bishop_pair: bishop_pair(board, color, params),
+            king_safety: king_safety(board, color, params),
+            mobility: mobility(board, color, params),
+            rooks: rooks(board, color, params),
+            outposts: outposts(board, color, params),
+            bad_bishops: bad_bishops(board, color, params),
+            ..SideTerms::default()
+        }
+    }
+
+    /// Every term with its name, in the order they are shown.
+    pub fn terms(&self) -> [(&'static str, Score); 9] {
+        [
+            ("material", self.material),
+            ("pst", self.pst),
+            ("bishop pair", self.bishop_pair),
+            ("pawns", self.pawns),
+            ("king safety", self.king_safety),
+            ("mobility", self.mobility),
+            ("rooks", self.rooks),
+            ("outposts", self.outposts),
+            ("bad bishops", self.bad_bishops),
+        ]
+    }
+
+    pub fn sum(&self) -> Score {
+        self.terms().iter().fold(Score::default(), |sum, (_, s)| sum + *s)
+    }
+}
+
+/// The evaluation of a position term by term for each side, see `explain_eval`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EvalBreakdown {
+    pub white: SideTerms,
+    pub black: SideTerms,
+    /// `max_phase` in the opening, 0 with only kings and pawns left
+    pub phase: i32,
+    /// the phase of the full set of pieces with the weights the position was evaluated with
+    pub max_phase: i32,
+}
+
+impl EvalBreakdown {
+    /// The evaluation from white's point of view, always the same as `evaluate`.
+    pub fn total(&self) -> i32 {
+        let sum = self.white.sum() - self.black.sum();
+        taper(sum.mg, sum.eg, self.phase, self.max_phase)
+    }
+}
+
+impl fmt::Display for EvalBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<12}{:>7}{:>7}{:>9}{:>7}{:>9}", "term", "white", "", "black", "", "net")?;
+        writeln!(f, "{:<12}{:>7}{:>7}{:>9}{:>7}", "", "mg", "eg", "mg", "eg")?;
+        for ((name, white), (_, black)) in self.white.terms().iter().zip(self.black.terms().iter()) {
+            let net = *white - *black;
+            writeln!(f, "{:<12}{:>7}{:>7}{:>9}{:>7}{:>9}", name, white.mg, white.eg, black.mg, black.eg, taper(net.mg, net.eg, self.phase, self.max_phase))?;
+        }
+        writeln!(f, "phase {} of {}", self.phase.min(self.max_phase), self.max_phase)?;
+        write!(f, "total {} from white's point of view", self.total())
+    }
+}
+
+/// Static evaluation of `board` from white's point of view with the default weights.
+pub fn evaluate(board: &Board) -> i32 {
+    evaluate_params(board, &EvalParams::default())
+}
+
+/// Static evaluation of `board` from white's point of view with the weights `params`.
+pub fn evaluate_params(board: &Board, params: &EvalParams) -> i32 {
+    evaluate_with(board, &Psqt::new(board, params), pawn_structure(board, params), params)
+}
+
+/// Static evaluation of `board` with the piece-square scores and the pawn structure already at hand,
+/// both computed with `params`.
+/// This is the evaluation of the search, `explain_eval` adds up the same terms side by side.
+pub fn evaluate_with(board: &Board, psqt: &Psqt, pawns: Score, params: &EvalParams) -> i32 {
+    let positional = SideTerms::positional(board, Color::White, params).sum() - SideTerms::positional(board, Color::Black, params).sum();
+    let sum = score(psqt.mg, psqt.eg) + pawns + positional;
+    taper(sum.mg, sum.eg, psqt.phase, params.max_phase())
+}
+
+/// `evaluate` term by term, to see why the engine likes or dislikes a position.
+pub fn explain_eval(board: &Board) -> EvalBreakdown {
+    explain_eval_params(board, &EvalParams::default())
+}
+
+/// `evaluate_params` term by term.
+pub fn explain_eval_params(board: &Board, params: &EvalParams) -> EvalBreakdown {
+    let side = |color| {
+        SideTerms {
+            pst: piece_squares(board, color, params),
+            pawns: side_structure(board, color, params),
+            ..SideTerms::positional(board, color, params)
+        }
+    };
+    EvalBreakdown {
+        white: side(Color::White),
+        black: side(Color::Black),
+        phase: Psqt::new(board, params).phase,
+        max_phase: params.max_phase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const POSITIONS: [&str; 6] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2",
+        "r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+    ];
+
+    // the same position with the board flipped vertically and the colours swapped
+    fn mirror(fen: &str) -> String {
+        let fields = fen.split(' ').collect::<Vec<&str>>();
+        let swap_case = |s: &str| s.chars().map(|c| if c.is_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect::<String>();
+        let ranks = fields[0].split('/').rev().map(swap_case).collect::<Vec<String>>().join("/");
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        let mut castling = swap_case(fields[2]).chars().collect::<Vec<char>>();
+        castling.sort_by_key(|c| (c.is_lowercase(), "KQkq".find(c.to_ascii_uppercase()).unwrap_or(0)));
+        let castling = castling.into_iter().collect::<String>();
+        let en_passant = fields[3].replace('3', "x").replace('6', "3").replace('x', "6");
+        format!("{} {} {} {} {} {}", ranks, side, castling, en_passant, fields[4], fields[5])
+    }
+
+    #[test]
+    fn test_symmetry() {
+        assert_eq!(evaluate(&Board::default()), 0);
+        for fen in POSITIONS.iter() {
+            let board = Board::from_str(fen).unwrap();
+            let mirrored = Board::from_str(&mirror(fen)).unwrap();
+            assert_eq!(evaluate(&mirrored), -evaluate(&board), "{}", fen);
+            let p = EvalParams::default();
+            assert_eq!(Psqt::new(&mirrored, &p).phase, Psqt::new(&board, &p).phase);
+        }
+    }
+
+    #[test]
+    fn test_phase() {
+        let p = EvalParams::default();
+        assert_eq!(p.max_phase(), 24);
+        assert_eq!(Psqt::new(&Board::default(), &p).phase, p.max_phase());
+        let board = Board::from_str("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(Psqt::new(&board, &p).phase, 4);
+        // with only pawns left the endgame tables decide alone
+        let board = Board::from_str("8/8/4k3/8/4K3/4P3/8/8 w - - 0 1").unwrap();
+        let psqt = Psqt::new(&board, &p);
+        assert_eq!(psqt.phase, 0);
+        assert_eq!(taper(psqt.mg, psqt.eg, psqt.phase, p.max_phase()), psqt.eg);
+        // so does a phase no piece counts towards
+        assert_eq!(taper(100, 50, 0, 0), 50);
+    }
+
+    #[test]
+    fn test_incremental() {
+        // every legal move two plies deep, castling, en passant and promotions included
+        let mut fens = POSITIONS.to_vec();
+        fens.push("4k3/1P6/8/8/8/8/6p1/4K2R w K - 0 1");
+        // with weights of its own, the updates have to use them too
+        let mut p = EvalParams::default();
+        p.pst[Piece::Rook.to_index()][7][5] = score(40, -30);
+        p.pst[Piece::Queen.to_index()][0][1] = score(-25, 60);
+        p.phase[Piece::Pawn.to_index()] = 1;
+        for fen in fens {
+            let board = Board::from_str(fen).unwrap();
+            let psqt = Psqt::new(&board, &p);
+            for m in MoveGen::new_legal(&board) {
+                let child = board.make_move_new(m);
+                let child_psqt = psqt.make_move(&board, m, &p);
+                assert_eq!(child_psqt, Psqt::new(&child, &p), "{} {}", fen, m);
+                for reply in MoveGen::new_legal(&child) {
+                    assert_eq!(child_psqt.make_move(&child, reply, &p), Psqt::new(&child.make_move_new(reply), &p), "{} {} {}", fen, m, reply);
+                }
+            }
+        }
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::from_str(fen).unwrap()
+    }
+
+    #[test]
+    fn test_king_safety() {
+        let p = EvalParams::default();
+        // castled behind three pawns
+        assert_eq!(king_safety(&board("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1"), Color::White, &p), p.pawn_shield * 3);
+        // no pawns left and the queen looks down the g file at g2 and g1
+        let exposed = board("4k3/8/8/6q1/8/8/8/6K1 w - - 0 1");
+        assert_eq!(king_safety(&exposed, Color::White, &p), p.king_open_file * 3 + p.king_zone_attack * 2);
+    }
+
+    #[test]
+    fn test_piece_terms() {
+        let p = EvalParams::default();
+        // a knight in the corner reaches b3 and c2
+        assert_eq!(mobility(&board("4k3/8/8/8/8/8/8/N3K3 w - - 0 1"), Color::White, &p), p.mobility[0] * 2);
+        let position = board("4k3/R7/8/3N4/2P5/8/8/4K3 w - - 0 1");
+        assert_eq!(rooks(&position, Color::White, &p), p.rook_open_file + p.rook_seventh);
+        assert_eq!(outposts(&position, Color::White, &p), p.knight_outpost);
+        // a pawn on e6 can chase the knight away
+        assert_eq!(outposts(&board("4k3/R7/4p3/3N4/2P5/8/8/4K3 w - - 0 1"), Color::White, &p), Score::default());
+        // both pawns are on dark squares like the bishop
+        assert_eq!(bad_bishops(&board("4k3/8/8/8/8/2P1P3/3B4/4K3 w - - 0 1"), Color::White, &p), p.bad_bishop * 2);
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut fens = POSITIONS.to_vec();
+        fens.push("4k3/R7/4p3/3N4/2P5/8/8/4K3 w - - 0 1");
+        for fen in fens {
+            let board = board(fen);
+            let breakdown = explain_eval(&board);
+            assert_eq!(breakdown.total(), evaluate(&board), "{}", fen);
+            assert_eq!(breakdown.phase, Psqt::new(&board, &EvalParams::default()).phase);
+        }
+        let breakdown = explain_eval(&Board::default());
+        assert_eq!(breakdown.white, breakdown.black);
+        assert_eq!(breakdown.white.material, score(4039, 3868));
+        let text = breakdown.to_string();
+        assert!(text.contains("king safety"));
+        assert!(text.ends_with("total 0 from white's point of view"));
+    }
+}
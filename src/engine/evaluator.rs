@@ -0,0 +1,138 @@
+use chess::*;
+
+use super::eval::{self, Psqt};
+use super::params::EvalParams;
+use super::pawns::PawnTable;
+
+/// A static evaluation the search can use. Every search is generic over it, so an evaluation of your own,
+/// `Classical` with other weights or the network of `nnue` all go through the same `Searcher` or `SearchThread`,
+/// which is also how to compare two of them. Mates and stalemates are scored by the search, the evaluator only
+/// sees positions with legal moves.
+///
+/// Most evaluations are cheaper to update after a move than to compute again, so every position of a search
+/// comes with a `State`: the root's is computed with `state`, every other one is derived from its parent's with
+/// `make_move`. Taking a move back needs nothing, the parent's state is still there. The search keeps one state
+/// per ply and writes every child's over the state of the last position it searched at that ply, so a state that
+/// owns memory can reuse it instead of allocating for every node.
+/// Every search thread works on a clone of the evaluator, so it may keep caches of its own.
+/// An evaluation that has nothing to update uses `()` as its state.
+pub trait Evaluator: Clone + Send + Sync {
+    /// What the evaluation keeps up to date from move to move.
+    type State: Clone + Send;
+
+    /// The state of `board` computed from scratch.
+    fn state(&self, board: &Board) -> Self::State;
+
+    /// Sets `child` to the state after `m` is played on `board`, whose state is `state`. What `child` held before
+    /// belongs to some other position.
+    fn make_move(&self, state: &Self::State, board: &Board, m: ChessMove, child: &mut Self::State);
+
+    /// Evaluation of `board` in centipawns from white's point of view, `state` belongs to `board`.
+    fn evaluate(&mut self, board: &Board, state: &Self::State) -> i32;
+}
+
+/// The hand-written evaluation of `eval` with the weights `params`. Piece-square scores are updated
+/// incrementally, pawn structures are looked up in a pawn table.
+pub struct Classical {
+    pub params: EvalParams,
+    pawns: PawnTable,
+}
+
+impl Classical {
+    pub fn new(params: EvalParams) -> Self {
+        Classical {
+            params,
+            pawns: PawnTable::new(),
+        }
+    }
+}
+
+impl Default for Classical {
+    fn default() -> Self {
+        Classical::new(EvalParams::default())
+    }
+}
+
+// the pawn table is a cache, a clone starts with an empty one instead of copying it
+impl Clone for Classical {
+    fn clone(&self) -> Self {
+        Classical::new(self.params)
+    }
+}
+
+impl Evaluator for Classical {
+    type State = Psqt;
+
+    fn state(&self, board: &Board) -> Psqt {
+        Psqt::new(board, &self.params)
+    }
+
+    fn make_move(&self, state: &Psqt, board: &Board, m: ChessMove, child: &mut Psqt) {
+        *child = state.make_move(board, m, &self.params);
+    }
+
+    fn evaluate(&mut self, board: &Board, state: &Psqt) -> i32 {
+        eval::evaluate_with(board, state, self.pawns.get(board, &self.params), &self.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::searcher::{SearchConfig, Searcher};
+    use crate::engine::tt::TranspositionTable;
+    use std::str::FromStr;
+
+    // counts material and nothing else
+    #[derive(Clone)]
+    struct Material;
+
+    impl Evaluator for Material {
+        type State = ();
+
+        fn state(&self, _: &Board) {}
+
+        fn make_move(&self, _: &(), _: &Board, _: ChessMove, _: &mut ()) {}
+
+        fn evaluate(&mut self, board: &Board, _: &()) -> i32 {
+            let pieces = [(Piece::Pawn, 100), (Piece::Knight, 300), (Piece::Bishop, 300), (Piece::Rook, 500), (Piece::Queen, 900)];
+            let material = |color| pieces.iter().map(|&(piece, value)| (board.pieces(piece) & board.color_combined(color)).popcnt() as i32 * value).sum::<i32>();
+            material(Color::White) - material(Color::Black)
+        }
+    }
+
+    #[test]
+    fn test_classical() {
+        let mut params = EvalParams {
+            rook_seventh: eval::score(60, 60),
+            ..EvalParams::default()
+        };
+        params.pst[Piece::Knight.to_index()][2][5] = eval::score(90, 10);
+        let mut classical = Classical::new(params);
+        let board = Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let state = classical.state(&board);
+        assert_eq!(classical.evaluate(&board, &state), eval::evaluate_params(&board, &params));
+        // every move of the position, castling and captures among them
+        let mut incremental = state;
+        for m in MoveGen::new_legal(&board) {
+            let child = board.make_move_new(m);
+            classical.make_move(&state, &board, m, &mut incremental);
+            assert_eq!(incremental, classical.state(&child));
+            assert_eq!(classical.evaluate(&child, &incremental), eval::evaluate_params(&child, &params));
+        }
+    }
+
+    #[test]
+    fn test_custom_evaluator() {
+        // the same search with either evaluation
+        let board = Board::from_str("5k2/8/3q4/8/8/8/8/1K1Q4 w - - 0 1").unwrap();
+        let table = TranspositionTable::new(1);
+        let material = Searcher::with_evaluator(SearchConfig::full(4), &table, Material).search(&board, |_| ());
+        assert_eq!(material.best_move, Some(ChessMove::from_san(&board, "Qxd6").unwrap()));
+        assert_eq!(material.score, 900);
+        table.clear();
+        let classical = Searcher::new(SearchConfig::full(4), &table).search(&board, |_| ());
+        assert_eq!(classical.best_move, material.best_move);
+        assert_ne!(classical.score, material.score);
+    }
+}
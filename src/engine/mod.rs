@@ -1,251 +1,46 @@
 use chess::*;
 
-use std::sync::{Arc, Mutex};
-use std::cmp::{max, min};
-
-use rayon::prelude::*;
-use counts::*;
-use negamax::*;
+use searcher::{SearchConfig, Searcher};
+use tt::TranspositionTable;
 
 pub mod negamax;
 pub mod counts;
+pub mod eval;
+pub mod evaluator;
+pub mod nnue;
+pub mod ordering;
+pub mod params;
+pub mod pawns;
+pub mod searcher;
+pub mod stats;
+pub mod time;
+pub mod tt;
+pub mod worker;
 
-fn evaluate_board(board: &Board, move_color: bool) -> i32 {
-    let moves = MoveGen::new_legal(&board);
-    if moves.len() == 0 {
-        if board.checkers().to_size(0) == 0 {
-            return 0;
-        } else {
-            return i32::MIN;
-        }
-    }
-    let mut eval = get_piece_values(board, Color::White) - get_piece_values(board, Color::Black);
-    let bishops = board.pieces(Piece::Bishop);
-    if (bishops & board.color_combined(Color::White)).popcnt() == 2 {
-        eval += 50;
-    }
-    if (bishops & board.color_combined(Color::Black)).popcnt() == 2 {
-        eval += 50; 
-    }
-    if move_color {
-        eval
-    } else {
-        -eval
-    }
+pub fn find_best_move_iterative(board: Board, depth: i8) -> Option<ChessMove> {
+    let config = SearchConfig {
+        iterative_deepening: true,
+        quiescence: false,
+        ..SearchConfig::plain(depth)
+    };
+    Searcher::new(config, &TranspositionTable::new(0)).search(&board, |_| ()).best_move
 }
 
-fn get_piece_values(board: &Board, color: Color) -> i32 {
-    let mut val = 0;
-    // queens
-    val += (board.color_combined(color) & board.pieces(Piece::Queen)).popcnt() * 950;
-    val += (board.color_combined(color) & board.pieces(Piece::Rook)).popcnt() * 560;
-    val += (board.color_combined(color) & board.pieces(Piece::Bishop)).popcnt() * 330;
-    val += (board.color_combined(color) & board.pieces(Piece::Knight)).popcnt() * 300;
-    val += (board.color_combined(color) & board.pieces(Piece::Pawn)).popcnt() * 100;
-    val as i32
-}
-
-pub fn find_best_move_iterative(board: Board, depth: usize, move_color: bool) -> ChessMove {
-    fn find_best_move_sorted_moves(board: Board, depth: usize, move_color: bool, moves: Vec<ChessMove>) -> Vec<ChessMove> { 
-        assert!(depth % 2 == 0);
-        fn internal_fn(board: Board, depth: usize, mut alpha: i32, mut beta: i32, maximizing: bool, move_color: bool) -> i32 {
-            if depth == 0 {
-                let eval = evaluate_board(&board, move_color);
-                return eval;
-            }
-            if maximizing {
-                let moves = MoveGen::new_legal(&board);
-                if moves.len() == 0 {
-                    if board.checkers().to_size(0) == 0 {
-                        return 0;
-                    } else {
-                        return i32::MIN;
-                    }
-                }
-                let mut best_value = i32::MIN;
-                for m in moves {
-                    let calculation = internal_fn(board.make_move_new(m), depth - 1, alpha, beta, false, move_color);
-                    best_value = max(best_value, calculation);
-                    alpha = max(best_value, alpha);
-                    if alpha >= beta {
-                        break;
-                    }
-                }
-                return best_value;
-            } else {
-                let moves = MoveGen::new_legal(&board);
-                if moves.len() == 0 {
-                    if board.checkers().to_size(0) == 0 {
-                        return 0;
-                    } else {
-                        return i32::MAX;
-                    }
-                }
-                let mut best_value = i32::MAX;
-                for m in moves {
-                    let calculation = internal_fn(board.make_move_new(m), depth - 1, alpha, beta, true, move_color);
-                    best_value = min(best_value, calculation);
-                    beta = min(best_value, beta);
-                    if alpha >= beta {
-                        break;
-                    }
-                }
-                return best_value;
-            }
-        }
-        let mut alpha = i32::MIN;
-        let beta = i32::MAX;
-        let mut best = i32::MIN;
-        let mut move_values = vec![];
-        for m in moves {
-            let calculation = internal_fn(board.make_move_new(m), depth - 1, alpha, beta, false, move_color);
-            move_values.push((calculation, m));
-            best = max(best, calculation);
-            alpha = max(best, alpha);
-            
-        }
-        move_values[..].sort_by(|a, b| std::cmp::Ordering::reverse(a.0.cmp(&b.0)));
-        move_values.iter().map(|v| v.1).collect::<Vec<ChessMove>>()
-    }
-    let mut moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
-    let mut run_depth = 2;
-    while run_depth <= depth {
-        moves = find_best_move_sorted_moves(board, run_depth, move_color, moves);
-        run_depth += 2;
-    }
-    moves.remove(0)
-}
-
-// move_color: true = white, false = black
-pub fn find_best_move_single(board: Board, depth: usize, move_color: bool) -> ChessMove { 
-    assert!(depth % 2 == 0);
-    fn internal_fn(board: Board, depth: usize, mut alpha: i32, mut beta: i32, maximizing: bool, move_color: bool, mov: ChessMove) -> (i32, ChessMove) {
-        if depth == 0 {
-            let eval = evaluate_board(&board, move_color);
-            return (eval, mov);
-        }
-        if maximizing {
-            let moves = MoveGen::new_legal(&board);
-            if moves.len() == 0 {
-                if board.checkers().to_size(0) == 0 {
-                    return (0, mov);
-                } else {
-                    return (i32::MIN, mov);
-                }
-            }
-            let mut best_value = (i32::MIN, mov);
-            for m in moves {
-                let calculation = internal_fn(board.make_move_new(m), depth - 1, alpha, beta, false, move_color, m);
-                best_value = (max(best_value.0, calculation.0), mov);
-                alpha = max(best_value.0, alpha);
-                if alpha >= beta {
-                    break;
-                }
-            }
-            return best_value;
-        } else {
-            let moves = MoveGen::new_legal(&board);
-            if moves.len() == 0 {
-                if board.checkers().to_size(0) == 0 {
-                    return (0, mov);
-                } else {
-                    return (i32::MAX, mov);
-                }
-            }
-            let mut best_value = (i32::MAX, mov);
-            for m in moves {
-                let calculation = internal_fn(board.make_move_new(m), depth - 1, alpha, beta, true, move_color, m);
-                best_value = (min(best_value.0, calculation.0), mov);
-                beta = min(best_value.0, beta);
-                if alpha >= beta {
-                    break;
-                }
-            }
-            return best_value;
-        }
-    }
-    let moves = MoveGen::new_legal(&board);
-    let mut best_value = (i32::MIN, moves.last().unwrap());
-    let mut alpha = i32::MIN;
-    let beta = i32::MAX;
-    for m in MoveGen::new_legal(&board) {
-        let calculation = internal_fn(board.make_move_new(m), depth - 1, alpha, beta, false, move_color, m);
-        if calculation.0 > best_value.0 {
-            best_value = (calculation.0, m);
-        }
-        alpha = max(best_value.0, alpha);
-        
-    }
-    best_value.1
-}
-
-fn find_best_move_sorted_moves(board: Board, depth: usize, move_color: bool, moves: Vec<ChessMove>) -> Vec<ChessMove> { 
-    assert!(depth % 2 == 0);
-    fn internal_fn(board: Board, depth: usize, mut alpha: i32, mut beta: i32, maximizing: bool, move_color: bool) -> i32 {
-        if depth == 0 {
-            let eval = evaluate_board(&board, move_color);
-            return eval;
-        }
-        if maximizing {
-            let moves = MoveGen::new_legal(&board);
-            if moves.len() == 0 {
-                if board.checkers().to_size(0) == 0 {
-                    return 0;
-                } else {
-                    return i32::MIN;
-                }
-            }
-            let mut best_value = i32::MIN;
-            for m in moves {
-                let calculation = internal_fn(board.make_move_new(m), depth - 1, alpha, beta, false, move_color);
-                best_value = max(best_value, calculation);
-                alpha = max(best_value, alpha);
-                if alpha >= beta {
-                    break;
-                }
-            }
-            return best_value;
-        } else {
-            let moves = MoveGen::new_legal(&board);
-            if moves.len() == 0 {
-                if board.checkers().to_size(0) == 0 {
-                    return 0;
-                } else {
-                    return i32::MAX;
-                }
-            }
-            let mut best_value = i32::MAX;
-            for m in moves {
-                let calculation = internal_fn(board.make_move_new(m), depth - 1, alpha, beta, true, move_color);
-                best_value = min(best_value, calculation);
-                beta = min(best_value, beta);
-                if alpha >= beta {
-                    break;
-                }
-            }
-            return best_value;
-        }
-    }
-    let mut alpha = i32::MIN;
-    let beta = i32::MAX;
-    let mut best = i32::MIN;
-    let mut move_values = vec![];
-    for m in moves {
-        let calculation = internal_fn(board.make_move_new(m), depth - 1, alpha, beta, false, move_color);
-        move_values.push((calculation, m));
-        best = max(best, calculation);
-        alpha = max(best, alpha);
-        
-    }
-    move_values[..].sort_by(|a, b| std::cmp::Ordering::reverse(a.0.cmp(&b.0)));
-    move_values.iter().map(|v| v.1).collect::<Vec<ChessMove>>()
+pub fn find_best_move_single(board: Board, depth: i8) -> Option<ChessMove> { 
+    let config = SearchConfig {
+        quiescence: false,
+        ..SearchConfig::plain(depth)
+    };
+    Searcher::new(config, &TranspositionTable::new(0)).search(&board, |_| ()).best_move
 }
 
 #[cfg(test)]
 mod tests {
     use lazy_static::lazy_static;
 
-    use crate::engine::negamax::find_best_move_nega;
+    use crate::engine::counts::*;
+    use crate::engine::evaluator::{Classical, Evaluator};
+    use crate::engine::negamax::*;
 
     use super::*;
     use std::time;
@@ -267,120 +62,123 @@ mod tests {
     #[test]
     fn test_mate_single() {
         let board = Board::from_str("7k/8/8/8/8/8/5R2/1K4R1 w - - 0 1").unwrap();
-        assert_eq!(find_best_move_single(board, 6, true), ChessMove::from_san(&board, "Rh2").unwrap());
+        assert_eq!(find_best_move_single(board, 6).unwrap(), ChessMove::from_san(&board, "Rh2").unwrap());
     }
 
     #[test]
     fn test_take_queen_single() {
         let board = Board::from_str("5k2/8/3q4/8/8/8/8/1K1Q4 w - - 0 1").unwrap();
-        assert_eq!(find_best_move_single(board, 4, true), ChessMove::from_san(&board, "Qxd6").unwrap());
+        assert_eq!(find_best_move_single(board, 4).unwrap(), ChessMove::from_san(&board, "Qxd6").unwrap());
+        // odd depths are fine too
+        assert_eq!(find_best_move_single(board, 3).unwrap(), ChessMove::from_san(&board, "Qxd6").unwrap());
+        assert_eq!(find_best_move_iterative_count(board, 3).unwrap(), ChessMove::from_san(&board, "Qxd6").unwrap());
     }
 
     #[test]
     fn test_mate_in_2() {
         let board = Board::from_str("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 0").unwrap();
-        assert_eq!(find_best_move_single(board, 6, true), ChessMove::from_san(&board, "Nf6+").unwrap());
+        assert_eq!(find_best_move_single(board, 6).unwrap(), ChessMove::from_san(&board, "Nf6+").unwrap());
     }
 
     #[test]
     fn test_mate_in_2_2() {
         let board = Board::from_str("7k/1p4p1/p4b1p/3N3P/2p5/2rb4/PP2r3/K2R2R1 b - - 0 1").unwrap();
-        assert_eq!(find_best_move_single(board, 4, false), ChessMove::from_san(&board, "Rc1+").unwrap());
+        assert_eq!(find_best_move_single(board, 4).unwrap(), ChessMove::from_san(&board, "Rc1+").unwrap());
     }
     #[test]
     fn test_win_piece() {
         let board = Board::from_str("r5k1/1R3bp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 1").unwrap();
-        let best = find_best_move_single(board, 6, true);
+        let best = find_best_move_single(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Rxf7").unwrap());
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_single(board, 4, false);
+        let best = find_best_move_single(board, 4).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Kxf7").unwrap());
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_single(board, 6, true);
+        let best = find_best_move_single(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Qb7+").unwrap());
     }
 
     #[test]
     fn test_win_piece_counts() {
         let board = Board::from_str("r5k1/1R3bp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 1").unwrap();
-        let best = find_best_move_single_count(board, 6, true);
+        let best = find_best_move_single_count(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Rxf7").unwrap());
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_single_count(board, 6, false);
+        let best = find_best_move_single_count(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Kxf7").unwrap());
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_single_count(board, 6, true);
+        let best = find_best_move_single_count(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Qb7+").unwrap());
     }
 
     #[test]
     fn test_win_piece_counts_2() {
         let board = Board::from_str("r5k1/1R3bp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 1").unwrap();
-        let best = find_best_move_single_count(board, 4, true);
-        let best = find_best_move_iterative_count(board, 6, true);
+        let best = find_best_move_single_count(board, 4).unwrap();
+        let best = find_best_move_iterative_count(board, 6).unwrap();
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_single_count(board, 4, false);
-        let best = find_best_move_iterative_count(board, 6, true);
+        let best = find_best_move_single_count(board, 4).unwrap();
+        let best = find_best_move_iterative_count(board, 6).unwrap();
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_single_count(board, 4, true);
-        let best = find_best_move_iterative_count(board, 6, true);
+        let best = find_best_move_single_count(board, 4).unwrap();
+        let best = find_best_move_iterative_count(board, 6).unwrap();
     }
 
     #[test]
     fn test_win_piece_2() {
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
-        assert_eq!(find_best_move_single(board, 4, true), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_single(board, 4).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
     }
 
     #[test]
     fn test_mate_in_2_inc() {
         let board = Board::from_str("7k/1p4p1/p4b1p/3N3P/2p5/2rb4/PP2r3/K2R2R1 b - - 0 1").unwrap();
-        assert_eq!(find_best_move_iterative(board, 8, false), ChessMove::from_san(&board, "Rc1+").unwrap());
+        assert_eq!(find_best_move_iterative(board, 8).unwrap(), ChessMove::from_san(&board, "Rc1+").unwrap());
     }
 
     #[test]
     fn test_mate_in_2_inc_nega() {
         let board = Board::from_str("7k/1p4p1/p4b1p/3N3P/2p5/2rb4/PP2r3/K2R2R1 b - - 0 1").unwrap();
-        assert_eq!(find_best_move_nega_iterative_transposition(board, 8), ChessMove::from_san(&board, "Rc1+").unwrap());
+        assert_eq!(find_best_move_nega_iterative_transposition(board, 8).unwrap(), ChessMove::from_san(&board, "Rc1+").unwrap());
     }
 
     #[test]
     fn test_win_piece_2_iter() {
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
-        assert_eq!(find_best_move_iterative(board, 8, true), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_iterative(board, 8).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
     }
 
     #[test]
     fn test_win_piece_cmp() {
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_single(board, 6, true), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_single(board, 6).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
         println!("single: {}", start.elapsed().as_secs_f32());
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega(board, 6), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_nega(board, 6).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
         println!("nega: {}", start.elapsed().as_secs_f32());
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_iterative(board, 6, true), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_iterative(board, 6).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
         println!("iter: {}", start.elapsed().as_secs_f32());
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega_iterative(board, 6), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_nega_iterative(board, 6).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
         println!("nega iter: {}", start.elapsed().as_secs_f32());
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega_iterative_transposition(board, 6), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_nega_iterative_transposition(board, 6).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
         println!("nega iter transpose: {}", start.elapsed().as_secs_f32());
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega_iterative_transposition_ordering(board, 6), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_nega_iterative_transposition_ordering(board, 6).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
         println!("nega iter transpose ordering: {}", start.elapsed().as_secs_f32());
     }
 
@@ -389,14 +187,14 @@ mod tests {
         let mut board = Board::default();
         let start = time::Instant::now();
         for _ in 0..10 {
-            let m = find_best_move_nega_iterative_transposition(board, 6);
+            let m = find_best_move_nega_iterative_transposition(board, 6).unwrap();
             board = board.make_move_new(m);
         }
         println!("{}", start.elapsed().as_secs_f32());
         let mut board = Board::default();
         let start = time::Instant::now();
         for _ in 0..10 {
-            let m = find_best_move_nega_iterative_transposition_ordering(board, 6);
+            let m = find_best_move_nega_iterative_transposition_ordering(board, 6).unwrap();
             board = board.make_move_new(m);
         }
         println!("{}", start.elapsed().as_secs_f32());
@@ -404,40 +202,45 @@ mod tests {
 
     #[test]
     fn test_eval() {
-        assert_eq!(evaluate_board(&Board::default(), true), 0);
-        assert_eq!(evaluate_board(&Board::default(), false), 0);
+        let mut classical = Classical::default();
+        let mut evaluate = |board: &Board| {
+            let state = classical.state(board);
+            classical.evaluate(board, &state)
+        };
+        assert_eq!(evaluate(&Board::default()), 0);
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
-        assert_eq!(evaluate_board(&board, true), -30);
-        assert_eq!(evaluate_board(&board, false), 30);
+        // black is up the exchange, whoever is to move
+        assert!(evaluate(&board) < 0);
+        assert_eq!(evaluate(&board.null_move().unwrap()), evaluate(&board));
     }
 
     #[test]
     fn test_win_piece_iterative() {
         let board = Board::from_str("r5k1/1R3bp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 1").unwrap();
-        let best = find_best_move_iterative(board, 6, true);
+        let best = find_best_move_iterative(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Rxf7").unwrap());
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_iterative(board, 6, false);
+        let best = find_best_move_iterative(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Kxf7").unwrap());
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_iterative(board, 6, true);
+        let best = find_best_move_iterative(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Qb7+").unwrap());
     }
 
     #[test]
     fn test_win_piece_nega_iterative() {
         let board = Board::from_str("r5k1/1R3bp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 1").unwrap();
-        let best = find_best_move_nega_iterative_transposition(board, 6);
+        let best = find_best_move_nega_iterative_transposition(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Rxf7").unwrap());
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_nega_iterative_transposition(board, 6);
+        let best = find_best_move_nega_iterative_transposition(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Kxf7").unwrap());
         println!("bruh");
         let board = board.make_move_new(best);
-        let best = find_best_move_nega_iterative_transposition(board, 6);
+        let best = find_best_move_nega_iterative_transposition(board, 6).unwrap();
         assert_eq!(best, ChessMove::from_san(&board, "Qb7+").unwrap());
     }
 
@@ -449,7 +252,7 @@ mod tests {
             let board = Board::from_str(board).unwrap();
             let mov = ChessMove::from_san(&board, m).unwrap();
             println!("board: {}", board);
-            assert_eq!(find_best_move_nega_iterative_transposition(board, 6), mov);
+            assert_eq!(find_best_move_nega_iterative_transposition(board, 6).unwrap(), mov);
             println!("took: {}", tmp.elapsed().as_secs_f32());
         }
         println!("{}", start.elapsed().as_secs_f32());
@@ -463,19 +266,155 @@ mod tests {
             let board = Board::from_str(board).unwrap();
             let mov = ChessMove::from_san(&board, m).unwrap();
             println!("board: {}", board);
-            assert_eq!(find_best_move_nega_iterative_transposition_ordering(board, 6), mov);
+            assert_eq!(find_best_move_nega_iterative_transposition_ordering(board, 6).unwrap(), mov);
             println!("took: {}", tmp.elapsed().as_secs_f32());
         }
         println!("{}", start.elapsed().as_secs_f32());
     }
 
+    #[test]
+    fn test_transposition_matches_negamax() {
+        for board in PUZZLES.keys() {
+            let board = Board::from_str(board).unwrap();
+            let color = match board.side_to_move() {
+                Color::Black => -1,
+                Color::White => 1,
+            };
+            for depth in 1..=4 {
+                let expected = negamax(board, depth, 0, -i32::MAX, i32::MAX, color);
+                let table = TranspositionTable::new(16);
+                assert_eq!(negamax_transposition(&board, depth, 0, -i32::MAX, i32::MAX, color, &table), expected);
+                let table = TranspositionTable::new(16);
+                assert_eq!(negamax_transposition_ordering(&board, depth, 0, -i32::MAX, i32::MAX, color, &table), expected);
+            }
+            assert_eq!(find_best_move_nega_transposition(board, 4).unwrap(), find_best_move_nega(board, 4).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_transposition_small_table() {
+        // a table far too small for the search still gives the same results, just slower
+        for board in PUZZLES.keys() {
+            let board = Board::from_str(board).unwrap();
+            let color = match board.side_to_move() {
+                Color::Black => -1,
+                Color::White => 1,
+            };
+            let table = TranspositionTable::new(0);
+            assert_eq!(negamax_transposition_ordering(&board, 4, 0, -i32::MAX, i32::MAX, color, &table), negamax(board, 4, 0, -i32::MAX, i32::MAX, color));
+        }
+    }
+
+    #[test]
+    fn test_pvs_matches_full_window() {
+        // null windows, aspiration windows and re-searches must not change the moves the full window search picks
+        for board in PUZZLES.keys() {
+            let board = Board::from_str(board).unwrap();
+            let result = find_best_move_nega_timed(board, 6, None, None, &TranspositionTable::new(16), |_| ());
+            assert_eq!(result.best_move, find_best_move_nega_iterative_transposition(board, 6));
+        }
+    }
+
+    // benchmark: nodes of a depth 6 iterative deepening search of every puzzle
+    fn puzzle_nodes<F: Fn(&mut SearchContext)>(setup: F) -> u64 {
+        let mut total = 0;
+        for board in PUZZLES.keys() {
+            let board = Board::from_str(board).unwrap();
+            let moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
+            let table = TranspositionTable::new(16);
+            let mut search = SearchContext::new(&table, None, None);
+            setup(&mut search);
+            for depth in (2..=6).step_by(2) {
+                find_best_move_nega_moves_transposition_ordering_context(&board, depth, &moves, -i32::MAX, i32::MAX, &mut search);
+            }
+            total += search.stats.nodes;
+        }
+        total
+    }
+
+    #[test]
+    fn test_history_nodes() {
+        let with = puzzle_nodes(|_| ());
+        let without = puzzle_nodes(|search| search.use_history = false);
+        println!("nodes with killers and history: {}, without: {}", with, without);
+        assert!(with < without);
+    }
+
+    #[test]
+    fn test_null_move_nodes() {
+        let with = puzzle_nodes(|_| ());
+        let without = puzzle_nodes(|search| search.pruning.null_move = false);
+        println!("nodes with null move pruning: {}, without: {}", with, without);
+        assert!(with < without);
+    }
+
+    // a kind of pruning and how to switch it off
+    type Toggle = (&'static str, fn(&mut PruningConfig));
+
+    #[test]
+    fn test_pruning_nodes() {
+        let with = puzzle_nodes(|_| ());
+        let toggles: [Toggle; 3] = [
+            ("late move reductions", |p| p.late_move_reductions = false),
+            ("futility pruning", |p| p.futility = false),
+            ("reverse futility pruning", |p| p.reverse_futility = false),
+        ];
+        for (name, switch_off) in &toggles {
+            let without = puzzle_nodes(|search| switch_off(&mut search.pruning));
+            println!("nodes with {}: {}, without: {}", name, with, without);
+            assert!(with < without);
+        }
+    }
+
+    #[test]
+    fn test_solve_puzzles_pruning() {
+        // every kind of pruning on its own still finds the solutions
+        let toggles: [fn(&mut PruningConfig); 4] = [
+            |p| p.null_move = true,
+            |p| p.late_move_reductions = true,
+            |p| p.futility = true,
+            |p| p.reverse_futility = true,
+        ];
+        for switch_on in &toggles {
+            let mut pruning = PruningConfig::none();
+            switch_on(&mut pruning);
+            for (board, m) in &*PUZZLES {
+                let board = Board::from_str(board).unwrap();
+                let config = SearchConfig {
+                    pruning,
+                    ..SearchConfig::full(6)
+                };
+                let result = Searcher::new(config, &TranspositionTable::new(16)).search(&board, |_| ());
+                assert_eq!(result.best_move, Some(ChessMove::from_san(&board, m).unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve_puzzles_threads() {
+        // helper threads change what the main thread finds in the table, but not the move it picks
+        for &threads in &[1, 2, 4] {
+            // a pool of its own, so the helpers run at the same time whatever the size of the global one
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            for (board, m) in &*PUZZLES {
+                let board = Board::from_str(board).unwrap();
+                let config = SearchConfig {
+                    threads,
+                    ..SearchConfig::full(6)
+                };
+                let result = pool.install(|| Searcher::new(config, &TranspositionTable::new(16)).search(&board, |_| ()));
+                assert_eq!(result.best_move, Some(ChessMove::from_san(&board, m).unwrap()));
+            }
+        }
+    }
+
     #[test]
     fn test_solve_puzzles_2() {
         for (board, m) in &*PUZZLES {
             let board = Board::from_str(board).unwrap();
             let mov = ChessMove::from_san(&board, m).unwrap();
             println!("board: {}", board);
-            assert_eq!(find_best_move_nega(board, 6), mov);
+            assert_eq!(find_best_move_nega(board, 6).unwrap(), mov);
         }
     }
 
@@ -485,11 +424,7 @@ mod tests {
             let board = Board::from_str(board).unwrap();
             let mov = ChessMove::from_san(&board, m).unwrap();
             println!("board: {}", board);
-            let side = match board.side_to_move() {
-                Color::Black => false,
-                Color::White => true,
-            };
-            assert_eq!(find_best_move_iterative(board, 6, side), mov);
+            assert_eq!(find_best_move_iterative(board, 6).unwrap(), mov);
         }
     }
 }
\ No newline at end of file
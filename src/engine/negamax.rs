@@ -1,297 +1,604 @@
 use chess::*;
 
-use std::sync::{Arc, Mutex};
-use std::cmp::{max, min};
-use std::collections::HashMap;
-
-fn evaluate_board(board: &Board) -> i32 {
-    let mut eval = get_piece_values(board, Color::White) - get_piece_values(board, Color::Black);
-    let bishops = board.pieces(Piece::Bishop);
-    if (bishops & board.color_combined(Color::White)).popcnt() == 2 {
-        eval += 50;
-    }
-    if (bishops & board.color_combined(Color::Black)).popcnt() == 2 {
-        eval -= 50; 
-    }
-    eval
-}
-
-fn evaluate_board_improved(board: &Board) -> i32 {
-    let eg_factor = (board.combined() ^ board.pieces(Piece::Pawn)).popcnt(); // 0 - 16 (or more)
-    let mut eval = get_piece_values(board, Color::White) - get_piece_values(board, Color::Black);
-    let bishops = board.pieces(Piece::Bishop);
-    if (bishops & board.color_combined(Color::White)).popcnt() == 2 {
-        eval += 50;
-    }
-    if (bishops & board.color_combined(Color::Black)).popcnt() == 2 {
-        eval -= 50; 
-    }
-    let pawns = board.pieces(Piece::Pawn);
-    let white_pawns = pawns & board.color_combined(Color::White);
-    let black_pawns = pawns & board.color_combined(Color::Black);
-    let mut white_pawn_bytes = vec![];
-    let mut white_pawns_combined = 0;
-    for i in 2..8 {
-        let tmp = (white_pawns.0 >> 8 * i & 255) as u8;
-        white_pawns_combined &= tmp;
-        white_pawn_bytes.push(tmp)
-    }
-
-    let mut black_pawn_bytes = vec![];
-    for i in 2..8 {
-        black_pawn_bytes.push((black_pawns.0 >> 8 * i & 255) as u8)
-    }
-    
-    let row_7 = (white_pawns.0 >> 56 & 255) as u8;
-    let row_6 = (white_pawns.0 >> 48 & 255) as u8;
-    let row_5 = (white_pawns.0 >> 40 & 255) as u8;
-    let row_4 = (white_pawns.0 >> 32 & 255) as u8;
-    let row_3 = (white_pawns.0 >> 24 & 255) as u8;
-    let row_2 = (white_pawns.0 >> 16 & 255) as u8;
-    eval
-}
-
-fn get_piece_values(board: &Board, color: Color) -> i32 {
-    let mut val = 0;
-    // queens
-    val += (board.color_combined(color) & board.pieces(Piece::Queen)).popcnt() * 950;
-    val += (board.color_combined(color) & board.pieces(Piece::Rook)).popcnt() * 560;
-    val += (board.color_combined(color) & board.pieces(Piece::Bishop)).popcnt() * 330;
-    val += (board.color_combined(color) & board.pieces(Piece::Knight)).popcnt() * 300;
-    val += (board.color_combined(color) & board.pieces(Piece::Pawn)).popcnt() * 100;
-    val as i32
-}
-
-pub fn negamax(board: Board, depth: i8, mut alpha: i32, beta: i32, color: i32) -> i32 {
-    let moves = MoveGen::new_legal(&board);
-    if moves.len() == 0 {
-        if board.checkers().popcnt() == 0 {
-            return 0;
-        } else {
-            return -i32::MAX;
-        }
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::cmp::max;
+use std::time::{Duration, Instant};
+
+use super::evaluator::{Classical, Evaluator};
+use super::ordering::{is_quiet, mvv_lva, order_moves, piece_value, see, MoveHistory};
+use super::searcher::{SearchConfig, SearchLimits, Searcher};
+use super::stats::SearchStats;
+use super::tt::{Bound, TranspositionTable, DEFAULT_HASH_MB};
+
+/// Score of a side that has been checkmated at the root. Being mated `ply` plies into the search
+/// scores `-(MATE - ply)`, so the winner prefers quicker mates and the loser slower ones.
+pub const MATE: i32 = 1_000_000;
+/// Deepest ply a search can reach, scores within `MAX_PLY` of `MATE` are mate scores.
+pub const MAX_PLY: usize = 128;
+
+/// Score of the side to move if it is checkmated `ply` plies from the root.
+pub fn mated_in(ply: usize) -> i32 {
+    -(MATE - ply as i32)
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE - MAX_PLY as i32
+}
+
+/// Converts a mate score into full moves until mate, positive if the side to move gives mate.
+pub fn mate_in_moves(score: i32) -> Option<i32> {
+    if !is_mate_score(score) {
+        return None;
     }
-    if depth == 0 {
-        return color * evaluate_board(&board)
+    let plies = MATE - score.abs();
+    if score > 0 {
+        Some((plies + 1) / 2)
+    } else {
+        Some(-(plies + 1) / 2)
     }
-    
-    let mut value = -i32::MAX;
-    for m in moves {
-        value = max(value, -negamax(board.make_move_new(m), depth - 1, -beta, -alpha, -color));
-        alpha = max(alpha, value);
-        if alpha >= beta {
-            break;
+}
+
+/// Plain alpha-beta search with quiescence search at the leaves.
+pub fn negamax(board: Board, depth: i8, ply: usize, alpha: i32, beta: i32, color: i32) -> i32 {
+    let table = TranspositionTable::new(0);
+    let mut search = Searcher::new(SearchConfig::plain(depth), &table).context(None, None);
+    negamax_transposition_ordering_context(&board, depth, ply, alpha, beta, color, &mut search)
+}
+
+/// `negamax` that also looks positions up in `table`.
+pub fn negamax_transposition(board: &Board, depth: i8, ply: usize, alpha: i32, beta: i32, color: i32, table: &TranspositionTable) -> i32 {
+    let config = SearchConfig {
+        transposition: true,
+        ..SearchConfig::plain(depth)
+    };
+    let mut search = Searcher::new(config, table).context(None, None);
+    negamax_transposition_ordering_context(board, depth, ply, alpha, beta, color, &mut search)
+}
+
+// what the result of a search with the window alpha..beta says about the real value
+fn bound(value: i32, alpha: i32, beta: i32) -> Bound {
+    if value <= alpha {
+        Bound::Upper
+    } else if value >= beta {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    }
+}
+
+/// `negamax_transposition` that also orders moves. Nothing is pruned, see `Searcher` for a search that does.
+pub fn negamax_transposition_ordering(board: &Board, depth: i8, ply: usize, alpha: i32, beta: i32, color: i32, table: &TranspositionTable) -> i32 {
+    let config = SearchConfig {
+        pruning: PruningConfig::none(),
+        ..SearchConfig::full(depth)
+    };
+    let mut search = Searcher::new(config, table).context(None, None);
+    negamax_transposition_ordering_context(board, depth, ply, alpha, beta, color, &mut search)
+}
+
+/// Pruning, reductions and extensions the search may use, everything that makes it look at some lines
+/// more closely than others. Every technique can be switched off on its own to compare node counts
+/// and results with and without it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PruningConfig {
+    /// skip a move and cut off if the opponent still can't reach beta, see `null_move_allowed`
+    pub null_move: bool,
+    /// search late quiet moves with less depth, and only search them again in full if they turn out better
+    pub late_move_reductions: bool,
+    /// skip quiet moves near the leaves when the static evaluation is too far below alpha for them to matter
+    pub futility: bool,
+    /// cut off near the leaves when the static evaluation is so far above beta that the opponent won't catch up
+    pub reverse_futility: bool,
+    /// search a ply deeper when in check or when there is only one legal move
+    pub extensions: bool,
+}
+
+impl Default for PruningConfig {
+    fn default() -> Self {
+        PruningConfig {
+            null_move: true,
+            late_move_reductions: true,
+            futility: true,
+            reverse_futility: true,
+            extensions: true,
         }
     }
-    value
 }
 
-pub fn negamax_transposition(board: &Board, depth: i8, mut alpha: i32, beta: i32, color: i32, table: &mut HashMap<u64, (i32, i8)>) -> i32 {
-    let moves = MoveGen::new_legal(&board);
-    if moves.len() == 0 {
-        let eval;
-        if board.checkers().popcnt() == 0 {
-            eval = 0;
-        } else {
-            eval = -i32::MAX;
+impl PruningConfig {
+    /// Nothing at all, the search returns the same scores as a plain alpha-beta search.
+    pub fn none() -> Self {
+        PruningConfig {
+            null_move: false,
+            late_move_reductions: false,
+            futility: false,
+            reverse_futility: false,
+            extensions: false,
         }
-        table.entry(board.get_hash()).or_insert((eval, 0));
-        return eval;
     }
-    if depth == 0 {
-        return color * evaluate_board(&board)
+}
+
+// how many plies less the search after a null move gets, on top of the ply of the null move itself
+const NULL_MOVE_R: i8 = 3;
+// shallower nodes gain too little from a null move search to pay for it
+const NULL_MOVE_MIN_DEPTH: i8 = 3;
+// from this depth on a null move cutoff is only taken if a reduced search without null move agrees
+const NULL_MOVE_VERIFY_DEPTH: i8 = 5;
+
+// the first moves of a node are the likeliest to be best, they are never reduced
+const LMR_MIN_MOVES: usize = 3;
+// from this depth on late moves are reduced
+const LMR_MIN_DEPTH: i8 = 3;
+// material a quiet move can be expected to gain at most, per ply of depth left
+const FUTILITY_MARGIN: i32 = 100;
+// futility and reverse futility pruning are only used this close to the leaves
+const FUTILITY_MAX_DEPTH: i8 = 2;
+const REVERSE_FUTILITY_MAX_DEPTH: i8 = 3;
+// most plies a line may be extended by, perpetual checks would extend it forever otherwise
+const MAX_EXTENSIONS: u8 = 8;
+
+fn non_pawn_material(board: &Board, color: Color) -> i32 {
+    let pieces = board.color_combined(color) & !board.pieces(Piece::Pawn) & !board.pieces(Piece::King);
+    pieces.map(|sq| piece_value(board.piece_on(sq).unwrap())).sum()
+}
+
+/// Whether passing the move is a fair test of the position. It isn't while in check, and in endgames where
+/// the side to move has nothing but pawns or a single minor piece, zugzwang is common there,
+/// so being forced to move may be the only reason a position is bad.
+pub fn null_move_allowed(board: &Board) -> bool {
+    board.checkers().popcnt() == 0 && non_pawn_material(board, board.side_to_move()) > piece_value(Piece::Bishop)
+}
+
+/// Bookkeeping shared by every node of a single search.
+pub struct SearchContext<'a, E: Evaluator = Classical> {
+    /// what the search has done so far, the iterations are filled in by the caller
+    pub stats: SearchStats,
+    // where positions are looked up and stored, only used if `transposition` is set
+    table: &'a TranspositionTable,
+    deadline: Option<Instant>,
+    stop: Option<Arc<AtomicBool>>,
+    // set once the deadline has passed or the stop flag was raised, the scores returned afterwards are meaningless
+    pub aborted: bool,
+    /// look positions up in the transposition table and store the results in it
+    pub transposition: bool,
+    /// search the likeliest best moves first and all but the first move of a node with a null window
+    pub ordering: bool,
+    /// search captures at the leaves, otherwise they are evaluated as they are
+    pub quiescence: bool,
+    /// also search quiet moves that give check on the first ply of the quiescence search
+    pub quiescence_checks: bool,
+    /// killer moves and history scores, kept over all iterations of the search
+    pub history: MoveHistory,
+    /// order quiet moves by `history`, only switched off to measure what it saves
+    pub use_history: bool,
+    pub pruning: PruningConfig,
+    /// the static evaluation of the nodes
+    pub evaluator: E,
+    // plies the line leading to the current node has been extended by
+    extended: u8,
+    // set right before searching the reply to a null move, two null moves in a row would prove nothing
+    after_null: bool,
+    // triangular table of principal variations, pv[ply] is the best line found so far from the node at that ply
+    pv: Vec<Vec<ChessMove>>,
+    // evaluator states handed down from the parent, states[ply] belongs to the position with the hash next to it.
+    // They are overwritten in place, a state is only allocated the first time a ply is reached
+    states: Vec<Option<(u64, E::State)>>,
+}
+
+impl<'a> SearchContext<'a> {
+    /// A context evaluating with the default weights of the classical evaluation.
+    pub fn new(table: &'a TranspositionTable, deadline: Option<Instant>, stop: Option<Arc<AtomicBool>>) -> Self {
+        SearchContext::with_evaluator(Classical::default(), table, deadline, stop)
     }
-    
-    let mut value = -i32::MAX;
-    for m in moves {
-        let new = board.make_move_new(m);
-        let hash = new.get_hash();
-        let eval;
-        match table.get(&hash) {
-            None => {
-                eval = -negamax_transposition(&new, depth - 1, -beta, -alpha, -color, table);
-                table.insert(hash, (eval, depth));
-            },
-            Some(vv) => {
-                if vv.1 >= depth {
-                    eval = vv.0;
-                } else {
-                    eval = -negamax_transposition(&new, depth - 1, -beta, -alpha, -color, table);
-                    table.insert(hash, (eval, depth));
-                }
-            },
+}
+
+impl<'a, E: Evaluator> SearchContext<'a, E> {
+    pub fn with_evaluator(evaluator: E, table: &'a TranspositionTable, deadline: Option<Instant>, stop: Option<Arc<AtomicBool>>) -> Self {
+        SearchContext {
+            stats: SearchStats::default(),
+            table,
+            deadline,
+            stop,
+            aborted: false,
+            transposition: true,
+            ordering: true,
+            quiescence: true,
+            quiescence_checks: false,
+            history: MoveHistory::new(),
+            use_history: true,
+            pruning: PruningConfig::default(),
+            evaluator,
+            extended: 0,
+            after_null: false,
+            pv: vec![],
+            states: vec![],
         }
-        value = max(value, eval);
-        alpha = max(alpha, value);
-        if alpha >= beta {
-            break;
+    }
+
+    /// The principal variation of the last completed root search.
+    pub fn pv(&self) -> &[ChessMove] {
+        self.pv.first().map(|pv| &pv[..]).unwrap_or(&[])
+    }
+
+    fn clear_pv(&mut self, ply: usize) {
+        if self.pv.len() < ply + 2 {
+            self.pv.resize(ply + 2, vec![]);
         }
+        self.pv[ply].clear();
     }
-    value
-}
 
-pub fn negamax_transposition_ordering(board: &Board, depth: i8, mut alpha: i32, beta: i32, color: i32, table: &mut HashMap<u64, (i32, i8)>) -> i32 {
-    let mut moves = MoveGen::new_legal(&board);
-    if moves.len() == 0 {
-        let eval;
-        if board.checkers().popcnt() == 0 {
-            eval = 0;
-        } else {
-            eval = -i32::MAX;
+    // m is the new best move at ply, followed by the best line of the child it leads to
+    fn update_pv(&mut self, ply: usize, m: ChessMove) {
+        let (parent, child) = self.pv.split_at_mut(ply + 1);
+        let line = &mut parent[ply];
+        line.clear();
+        line.push(m);
+        line.extend_from_slice(&child[0]);
+    }
+
+    // makes states[ply] the evaluator state of the node at ply, it's only computed from scratch
+    // if the parent didn't pass it down
+    fn load_state(&mut self, board: &Board, ply: usize) {
+        if self.states.len() <= ply {
+            self.states.resize_with(ply + 1, || None);
+        }
+        match &self.states[ply] {
+            Some((hash, _)) if *hash == board.get_hash() => (),
+            _ => self.states[ply] = Some((board.get_hash(), self.evaluator.state(board))),
         }
-        table.entry(board.get_hash()).or_insert((eval, 0));
-        return eval;
     }
-    if depth == 0 {
-        return color * evaluate_board(&board)
+
+    // hands the state of `child` down to ply + 1, `board` is the node at ply and its state is loaded.
+    // Without a move `child` is the null move of `board` and gets the same state
+    fn pass_state(&mut self, ply: usize, board: &Board, m: Option<ChessMove>, child: &Board) {
+        if self.states.len() <= ply + 1 {
+            self.states.resize_with(ply + 2, || None);
+        }
+        let (parents, children) = self.states.split_at_mut(ply + 1);
+        let state = match &parents[ply] {
+            Some((hash, state)) if *hash == board.get_hash() => state,
+            _ => panic!("the state of the parent isn't loaded"),
+        };
+        let (hash, child_state) = children[0].get_or_insert_with(|| (0, state.clone()));
+        *hash = child.get_hash();
+        match m {
+            Some(m) => self.evaluator.make_move(state, board, m, child_state),
+            None => child_state.clone_from(state),
+        }
     }
 
-    let pieces = if color == 1 {
-        board.color_combined(Color::Black)
-    } else {
-        board.color_combined(Color::White)
-    };
-    
-    let mut value = -i32::MAX;
-    moves.set_iterator_mask(*pieces);
-    for _ in 0..2 {
-        while let Some(m) = moves.next() {
-            let new = board.make_move_new(m);
-            let hash = new.get_hash();
-            let eval;
-            match table.get(&hash) {
-                None => {
-                    eval = -negamax_transposition_ordering(&new, depth - 1, -beta, -alpha, -color, table);
-                    table.insert(hash, (eval, depth));
-                },
-                Some(vv) => {
-                    if vv.1 >= depth {
-                        eval = vv.0;
-                    } else {
-                        eval = -negamax_transposition_ordering(&new, depth - 1, -beta, -alpha, -color, table);
-                        table.insert(hash, (eval, depth));
-                    }
-                },
+    // static evaluation of the node at ply from white's point of view
+    fn evaluate(&mut self, board: &Board, ply: usize) -> i32 {
+        self.load_state(board, ply);
+        let (_, state) = self.states[ply].as_ref().unwrap();
+        self.evaluator.evaluate(board, state)
+    }
+
+    // only looks at the clock and the stop flag on the first node and every couple thousand nodes after it,
+    // Instant::now() isn't free
+    fn should_stop(&mut self) -> bool {
+        if !self.aborted && self.stats.nodes % 2048 == 1 {
+            if let Some(stop) = &self.stop {
+                self.aborted = stop.load(Ordering::Relaxed);
             }
-            value = max(value, eval);
-            alpha = max(alpha, value);
-            if alpha >= beta {
-                return value;
+            if let Some(deadline) = self.deadline {
+                self.aborted |= Instant::now() >= deadline;
             }
         }
-        moves.set_iterator_mask(!EMPTY);
+        self.aborted
     }
-    value
 }
 
-pub fn find_best_move_nega(board: Board, depth: i8) -> ChessMove {
-    let color = match board.side_to_move() {
+// 1 if white is to move and -1 if black is, what white's evaluation is multiplied with to get the side to move's
+fn color_of(board: &Board) -> i32 {
+    match board.side_to_move() {
         Color::Black => -1,
         Color::White => 1,
-    };
-    let mut moves = MoveGen::new_legal(&board);
-    let mut alpha = -i32::MAX;
-    let beta = i32::MAX;
-    let mut best_move = moves.next().unwrap();
-    let mut best_value = -negamax(board.make_move_new(best_move), depth - 1, -beta, -alpha, -color);
-    for m in moves {
-        let calc = -negamax(board.make_move_new(m), depth - 1, -beta, -alpha, -color);
-        if best_value < calc {
-            best_value = calc;
-            best_move = m;
-        }
-        alpha = max(alpha, best_value);
+    }
+}
 
-        if alpha >= beta {
-            break;
+// principal variation search: only the first move of a node gets the full window. The others just have to be
+// shown worse than it with a null window, only a move that turns out better is searched again with the full window.
+// Returns the score of the child from the point of view of the parent.
+// Without move ordering every move gets the full window, the first move isn't likelier to be best than the others.
+fn pvs_child<E: Evaluator>(child: &Board, first: bool, depth: i8, ply: usize, alpha: i32, beta: i32, search: &mut SearchContext<E>) -> i32 {
+    let color = color_of(child);
+    if !first && search.ordering {
+        let eval = -negamax_transposition_ordering_context(child, depth, ply, -alpha - 1, -alpha, color, search);
+        if eval <= alpha || eval >= beta || search.aborted {
+            return eval;
         }
     }
-    best_move
+    -negamax_transposition_ordering_context(child, depth, ply, -beta, -alpha, color, search)
 }
 
-pub fn find_best_move_nega_transposition(board: Board, depth: i8) -> ChessMove {
-    let color = match board.side_to_move() {
-        Color::Black => -1,
-        Color::White => 1,
+pub fn negamax_transposition_ordering_context<E: Evaluator>(board: &Board, mut depth: i8, ply: usize, mut alpha: i32, beta: i32, color: i32, search: &mut SearchContext<E>) -> i32 {
+    search.stats.nodes += 1;
+    let after_null = std::mem::replace(&mut search.after_null, false);
+    if search.should_stop() {
+        return 0;
+    }
+    search.clear_pv(ply);
+    let hash = board.get_hash();
+    let entry = if search.transposition {
+        search.stats.tt_probes += 1;
+        search.table.probe(hash, ply)
+    } else {
+        None
     };
-    let mut moves = MoveGen::new_legal(&board);
-    let mut alpha = -i32::MAX;
-    let beta = i32::MAX;
-    let mut best_move = moves.next().unwrap();
-    let mut table = HashMap::new();
-    let mut best_value = -negamax_transposition(&board.make_move_new(best_move), depth - 1, -beta, -alpha, -color, &mut table);
-    for m in moves {
-        let calc = -negamax_transposition(&board.make_move_new(m), depth - 1, -beta, -alpha, -color, &mut table);
-        if best_value < calc {
-            best_value = calc;
-            best_move = m;
+    if entry.is_some() {
+        search.stats.tt_hits += 1;
+    }
+    if let Some(score) = entry.and_then(|e| e.cutoff(depth, alpha, beta)) {
+        search.stats.tt_cutoffs += 1;
+        return score;
+    }
+    let moves = MoveGen::new_legal(board);
+    if moves.len() == 0 {
+        if board.checkers().popcnt() == 0 {
+            return 0;
+        } else {
+            return mated_in(ply);
         }
-        alpha = max(alpha, best_value);
+    }
+    // forcing positions are searched a ply deeper so lines of checks and forced replies are seen to the end
+    let in_check = board.checkers().popcnt() != 0;
+    let extend = search.pruning.extensions && search.extended < MAX_EXTENSIONS && (in_check || moves.len() == 1);
+    if extend {
+        depth += 1;
+    }
+    if depth <= 0 {
+        if !search.quiescence {
+            return color * search.evaluate(board, ply);
+        }
+        return quiescence_context(board, 0, ply, alpha, beta, color, search)
+    }
+
+    // forward pruning is only tried in null window nodes, the principal variation is searched in full
+    let prunable = alpha.checked_add(1) == Some(beta) && !in_check && !is_mate_score(beta);
+    let static_eval = color * search.evaluate(board, ply);
+    if search.pruning.reverse_futility && prunable && depth <= REVERSE_FUTILITY_MAX_DEPTH && static_eval - FUTILITY_MARGIN * depth as i32 >= beta {
+        return static_eval - FUTILITY_MARGIN * depth as i32;
+    }
 
+    if search.pruning.null_move && !after_null && prunable && depth >= NULL_MOVE_MIN_DEPTH
+        && null_move_allowed(board) && static_eval >= beta {
+        if let Some(null) = board.null_move() {
+            search.after_null = true;
+            search.pass_state(ply, board, None, &null);
+            let eval = -negamax_transposition_ordering_context(&null, max(depth - 1 - NULL_MOVE_R, 0), ply + 1, -beta, -alpha, -color, search);
+            if search.aborted {
+                return 0;
+            }
+            if eval >= beta {
+                // re-search this node reduced and without a null move, so zugzwang that slipped through can't cause the cutoff
+                let verified = depth < NULL_MOVE_VERIFY_DEPTH || {
+                    search.after_null = true;
+                    negamax_transposition_ordering_context(board, depth - NULL_MOVE_R, ply, alpha, beta, color, search) >= beta
+                };
+                if search.aborted {
+                    return 0;
+                }
+                if verified {
+                    // a mate found after passing isn't a real mate
+                    return if is_mate_score(eval) { beta } else { eval };
+                }
+            }
+        }
+    }
+
+    // the best move of an earlier search of this position goes first
+    let no_history = MoveHistory::new();
+    let history = if search.use_history { &search.history } else { &no_history };
+    let moves = if search.ordering {
+        order_moves(board, moves, entry.and_then(|e| e.best_move), ply, history)
+    } else {
+        moves.collect()
+    };
+
+    let futile = search.pruning.futility && prunable && depth <= FUTILITY_MAX_DEPTH && static_eval + FUTILITY_MARGIN * depth as i32 <= alpha;
+    let alpha_orig = alpha;
+    let mut value = -i32::MAX;
+    let mut best_move = None;
+    if extend {
+        search.extended += 1;
+    }
+    for (i, m) in moves.into_iter().enumerate() {
+        let child = board.make_move_new(m);
+        // quiet moves that give check are neither pruned nor reduced, they may start a mating attack
+        let quiet = is_quiet(board, m) && child.checkers().popcnt() == 0;
+        if futile && quiet && i > 0 {
+            continue;
+        }
+        search.pass_state(ply, board, Some(m), &child);
+        let reduce = search.pruning.late_move_reductions && quiet && !in_check && depth >= LMR_MIN_DEPTH && i >= LMR_MIN_MOVES;
+        let eval = if reduce {
+            let reduction = if i >= 2 * LMR_MIN_MOVES { 2 } else { 1 };
+            let eval = -negamax_transposition_ordering_context(&child, max(depth - 1 - reduction, 0), ply + 1, -alpha - 1, -alpha, -color, search);
+            if eval > alpha && !search.aborted {
+                pvs_child(&child, false, depth - 1, ply + 1, alpha, beta, search)
+            } else {
+                eval
+            }
+        } else {
+            pvs_child(&child, i == 0, depth - 1, ply + 1, alpha, beta, search)
+        };
+        if search.aborted {
+            if extend {
+                search.extended -= 1;
+            }
+            return 0;
+        }
+        if eval > value || best_move.is_none() {
+            value = eval;
+            best_move = Some(m);
+        }
+        if eval > alpha {
+            search.update_pv(ply, m);
+        }
+        alpha = max(alpha, value);
         if alpha >= beta {
+            search.stats.beta_cutoffs += 1;
+            if i == 0 {
+                search.stats.first_move_cutoffs += 1;
+            }
+            if search.use_history && is_quiet(board, m) {
+                search.history.update(board.side_to_move(), ply, depth, m);
+            }
             break;
         }
     }
-    best_move
+    if extend {
+        search.extended -= 1;
+    }
+    if search.transposition {
+        search.table.store(hash, value, depth, ply, bound(value, alpha_orig, beta), best_move);
+    }
+    value
 }
 
-pub fn find_best_move_nega_moves(board: Board, depth: i8, moves: Vec<ChessMove>) -> Vec<ChessMove> {
-    let color = match board.side_to_move() {
-        Color::Black => -1,
-        Color::White => 1,
-    };
-    let mut alpha = -i32::MAX;
-    let beta = i32::MAX;
-    let first_move = moves[0];
-    alpha = -negamax(board.make_move_new(first_move), depth - 1, -beta, -alpha, -color);
-    let mut move_values = vec![];
-    move_values.push((first_move, alpha));
-    let mut best_value = alpha;
-    for m in moves {
-        let calc = -negamax(board.make_move_new(m), depth - 1, -beta, -alpha, -color);
-        if best_value < calc {
-            best_value = calc;
+pub fn quiescence(board: &Board, ply: usize, alpha: i32, beta: i32, color: i32) -> i32 {
+    let table = TranspositionTable::new(0);
+    quiescence_context(board, 0, ply, alpha, beta, color, &mut SearchContext::new(&table, None, None))
+}
+
+/// Searches the captures and queen promotions left at a leaf so it isn't evaluated in the middle of an exchange.
+/// The side to move can always stand pat, i.e. keep the static evaluation instead of making a move,
+/// unless it is in check, then every evasion is searched.
+/// `qdepth` counts the plies spent in quiescence search, 0 at the leaf of the main search.
+/// Quiet checks are only tried there and only if the search asks for them.
+pub fn quiescence_context<E: Evaluator>(board: &Board, qdepth: i8, ply: usize, mut alpha: i32, beta: i32, color: i32, search: &mut SearchContext<E>) -> i32 {
+    search.stats.nodes += 1;
+    search.stats.qnodes += 1;
+    if search.should_stop() {
+        return 0;
+    }
+    let mut moves = MoveGen::new_legal(board);
+    let in_check = board.checkers().popcnt() != 0;
+    if moves.len() == 0 {
+        if in_check {
+            return mated_in(ply);
+        } else {
+            return 0;
         }
-        move_values.push((m, calc));
-        alpha = max(alpha, best_value);
+    }
+    if ply >= MAX_PLY - 1 {
+        return color * search.evaluate(board, ply);
+    }
+    search.load_state(board, ply);
 
+    let mut value = -i32::MAX;
+    let candidates = if in_check {
+        moves.collect::<Vec<ChessMove>>()
+    } else {
+        value = color * search.evaluate(board, ply);
+        if value >= beta {
+            return value;
+        }
+        alpha = max(alpha, value);
+        let pieces = if color == 1 {
+            board.color_combined(Color::Black)
+        } else {
+            board.color_combined(Color::White)
+        };
+        // same masking as the main search, captures are the moves landing on an enemy piece
+        // or on the square behind a pawn that can be taken en passant
+        let ep = board.en_passant().map_or(EMPTY, |sq| {
+            BitBoard::from_square(sq.ubackward(!board.side_to_move()))
+        });
+        moves.set_iterator_mask(*pieces | ep);
+        let mut candidates = (&mut moves).collect::<Vec<ChessMove>>();
+        moves.set_iterator_mask(!EMPTY);
+        candidates.extend(moves.filter(|m| m.get_promotion() == Some(Piece::Queen)));
+        // captures and promotions that lose material can't raise the score above standing pat
+        candidates.retain(|&m| see(board, m) >= 0);
+        candidates.sort_by_key(|&m| -mvv_lva(board, m));
+        if qdepth == 0 && search.quiescence_checks {
+            let mut quiet = MoveGen::new_legal(board);
+            quiet.set_iterator_mask(!*board.combined());
+            candidates.extend(quiet.filter(|m| m.get_promotion().is_none() && board.make_move_new(*m).checkers().popcnt() != 0));
+        }
+        candidates
+    };
+
+    for m in candidates {
+        let child = board.make_move_new(m);
+        search.pass_state(ply, board, Some(m), &child);
+        let eval = -quiescence_context(&child, qdepth - 1, ply + 1, -beta, -alpha, -color, search);
+        if search.aborted {
+            return 0;
+        }
+        value = max(value, eval);
+        alpha = max(alpha, value);
         if alpha >= beta {
             break;
         }
     }
-    move_values[..].sort_by(|a, b| std::cmp::Ordering::reverse(a.1.cmp(&b.1)));
-    move_values.iter().map(|v| v.0).collect::<Vec<ChessMove>>()
+    value
 }
 
+pub fn find_best_move_nega(board: Board, depth: i8) -> Option<ChessMove> {
+    let table = TranspositionTable::new(0);
+    Searcher::new(SearchConfig::plain(depth), &table).search(&board, |_| ()).best_move
+}
 
+pub fn find_best_move_nega_transposition(board: Board, depth: i8) -> Option<ChessMove> {
+    let config = SearchConfig {
+        transposition: true,
+        ..SearchConfig::plain(depth)
+    };
+    Searcher::new(config, &TranspositionTable::new(DEFAULT_HASH_MB)).search(&board, |_| ()).best_move
+}
 
-pub fn find_best_move_nega_iterative(board: Board, depth: i8) -> ChessMove {
-    let mut moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
-    let mut run_depth = 2;
-    while run_depth <= depth {
-        moves = find_best_move_nega_moves(board, run_depth, moves);
-        run_depth += 2;
-    }
-    moves.remove(0)
+pub fn find_best_move_nega_moves(board: Board, depth: i8, moves: Vec<ChessMove>) -> Vec<ChessMove> {
+    let table = TranspositionTable::new(0);
+    Searcher::new(SearchConfig::plain(depth), &table).search_moves(&board, depth, &moves).iter().map(|v| v.0).collect::<Vec<ChessMove>>()
 }
 
-pub fn find_best_move_nega_moves_transposition(board: &Board, depth: i8, moves: Vec<ChessMove>) -> Vec<ChessMove> {
-    let color = match board.side_to_move() {
-        Color::Black => -1,
-        Color::White => 1,
+pub fn find_best_move_nega_iterative(board: Board, depth: i8) -> Option<ChessMove> {
+    let config = SearchConfig {
+        iterative_deepening: true,
+        ..SearchConfig::plain(depth)
+    };
+    Searcher::new(config, &TranspositionTable::new(0)).search(&board, |_| ()).best_move
+}
+
+pub fn find_best_move_nega_moves_transposition(board: &Board, depth: i8, moves: Vec<ChessMove>, table: &TranspositionTable) -> Vec<ChessMove> {
+    let config = SearchConfig {
+        transposition: true,
+        ..SearchConfig::plain(depth)
+    };
+    Searcher::new(config, table).search_moves(board, depth, &moves).iter().map(|v| v.0).collect::<Vec<ChessMove>>()
+}
+
+pub fn find_best_move_nega_iterative_transposition(board: Board, depth: i8) -> Option<ChessMove> {
+    let config = SearchConfig {
+        transposition: true,
+        iterative_deepening: true,
+        ..SearchConfig::plain(depth)
     };
-    let mut table = HashMap::new();
-    let mut alpha = -i32::MAX;
-    let beta = i32::MAX;
-    let first_move = moves[0];
-    alpha = -negamax_transposition(&board.make_move_new(first_move), depth - 1, -beta, -alpha, -color, &mut table);
+    Searcher::new(config, &TranspositionTable::new(DEFAULT_HASH_MB)).search(&board, |_| ()).best_move
+}
+
+pub fn find_best_move_nega_moves_transposition_ordering(board: &Board, depth: i8, moves: Vec<ChessMove>, table: &TranspositionTable) -> Vec<ChessMove> {
+    Searcher::new(SearchConfig::full(depth), table).search_moves(board, depth, &moves).iter().map(|v| v.0).collect::<Vec<ChessMove>>()
+}
+
+// returns the root moves sorted best first, together with their scores from the side to move's point of view.
+// If the best score is outside alpha..beta it is only a bound and the moves may be incomplete
+pub fn find_best_move_nega_moves_transposition_ordering_context<E: Evaluator>(board: &Board, depth: i8, moves: &[ChessMove], mut alpha: i32, beta: i32, search: &mut SearchContext<E>) -> Vec<(ChessMove, i32)> {
+    search.clear_pv(0);
+    search.load_state(board, 0);
     let mut move_values = vec![];
-    move_values.push((first_move, alpha));
-    let mut best_value = alpha;
-    for m in moves {
-        let calc = -negamax_transposition(&board.make_move_new(m), depth - 1, -beta, -alpha, -color, &mut table);
-        if best_value < calc {
+    let mut best_value = -i32::MAX;
+    for (i, &m) in moves.iter().enumerate() {
+        let child = board.make_move_new(m);
+        search.pass_state(0, board, Some(m), &child);
+        let calc = pvs_child(&child, i == 0, depth - 1, 1, alpha, beta, search);
+        if best_value < calc || i == 0 {
             best_value = calc;
+            search.update_pv(0, m);
         }
         move_values.push((m, calc));
         alpha = max(alpha, best_value);
@@ -301,61 +608,79 @@ pub fn find_best_move_nega_moves_transposition(board: &Board, depth: i8, moves:
         }
     }
     move_values[..].sort_by(|a, b| std::cmp::Ordering::reverse(a.1.cmp(&b.1)));
-    move_values.iter().map(|v| v.0).collect::<Vec<ChessMove>>()
+    move_values
 }
 
-pub fn find_best_move_nega_iterative_transposition(board: Board, depth: i8) -> ChessMove {
-    let mut moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
-    let mut run_depth = 2;
-    while run_depth <= depth {
-        moves = find_best_move_nega_moves_transposition(&board, run_depth, moves);
-        run_depth += 2;
-    }
-    moves.remove(0)
+pub fn find_best_move_nega_iterative_transposition_ordering(board: Board, depth: i8) -> Option<ChessMove> {
+    Searcher::new(SearchConfig::full(depth), &TranspositionTable::new(DEFAULT_HASH_MB)).search(&board, |_| ()).best_move
 }
 
-pub fn find_best_move_nega_moves_transposition_ordering(board: &Board, depth: i8, moves: Vec<ChessMove>) -> Vec<ChessMove> {
-    let color = match board.side_to_move() {
-        Color::Black => -1,
-        Color::White => 1,
-    };
-    let mut table = HashMap::new();
-    let mut alpha = -i32::MAX;
-    let beta = i32::MAX;
-    let first_move = moves[0];
-    alpha = -negamax_transposition_ordering(&board.make_move_new(first_move), depth - 1, -beta, -alpha, -color, &mut table);
-    let mut move_values = vec![];
-    move_values.push((first_move, alpha));
-    let mut best_value = alpha;
-    for m in moves {
-        let calc = -negamax_transposition_ordering(&board.make_move_new(m), depth - 1, -beta, -alpha, -color, &mut table);
-        if best_value < calc {
-            best_value = calc;
+// half the width of the first window tried around the score of the previous iteration
+const ASPIRATION_WINDOW: i32 = 50;
+
+// searches the root with a narrow window around the score of the previous iteration, which cuts off more
+// than a full window as long as the score doesn't change much. Every time the score falls outside the window
+// the search is repeated with the window widened on that side
+pub fn aspiration_search<E: Evaluator>(board: &Board, depth: i8, moves: &[ChessMove], previous: i32, search: &mut SearchContext<E>) -> Vec<(ChessMove, i32)> {
+    // the first iteration has nothing to centre the window on, and mate scores jump too far.
+    // Narrow windows are part of the move ordering, they cost more than they save with moves in random order
+    if depth <= 2 || is_mate_score(previous) || !search.ordering {
+        return find_best_move_nega_moves_transposition_ordering_context(board, depth, moves, -i32::MAX, i32::MAX, search);
+    }
+    let mut delta = ASPIRATION_WINDOW;
+    let mut alpha = previous - delta;
+    let mut beta = previous + delta;
+    loop {
+        let move_values = find_best_move_nega_moves_transposition_ordering_context(board, depth, moves, alpha, beta, search);
+        let score = move_values[0].1;
+        if search.aborted || (score > alpha && score < beta) {
+            return move_values;
         }
-        move_values.push((m, calc));
-        alpha = max(alpha, best_value);
-
-        if alpha >= beta {
-            break;
+        delta = delta.saturating_mul(4);
+        if score <= alpha {
+            alpha = max(score.saturating_sub(delta), -i32::MAX);
+        } else {
+            beta = score.saturating_add(delta);
         }
     }
-    move_values[..].sort_by(|a, b| std::cmp::Ordering::reverse(a.1.cmp(&b.1)));
-    move_values.iter().map(|v| v.0).collect::<Vec<ChessMove>>()
 }
 
-pub fn find_best_move_nega_iterative_transposition_ordering(board: Board, depth: i8) -> ChessMove {
-    let mut moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
-    let mut run_depth = 2;
-    while run_depth <= depth {
-        moves = find_best_move_nega_moves_transposition_ordering(&board, run_depth, moves);
-        run_depth += 2;
+/// Outcome of an iterative deepening search, also reported after every completed depth.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    /// `None` if the side to move is checkmated or stalemated
+    pub best_move: Option<ChessMove>,
+    /// score of `best_move` from the side to move's point of view
+    pub score: i32,
+    /// the line the engine expects to be played, starting with `best_move`
+    pub pv: Vec<ChessMove>,
+    /// depth of the last completed iteration, 0 if the search was stopped before finishing one
+    pub depth: i8,
+    /// what the search has done so far, the final result also counts what the helper threads did
+    pub stats: SearchStats,
+}
+
+/// Iterative deepening search that stops once `depth` is reached, `budget` runs out or `stop` is raised,
+/// whichever comes first. Returns the best move of the last completed iteration.
+///
+/// `table` is meant to be kept for the whole game, what earlier searches stored in it is used to order moves.
+/// The search has everything switched on and runs on one thread, a `Searcher` can be configured otherwise.
+pub fn find_best_move_nega_timed<F: FnMut(&SearchResult) + Send>(board: Board, depth: i8, budget: Option<Duration>, stop: Option<Arc<AtomicBool>>, table: &TranspositionTable, info: F) -> SearchResult {
+    let config = SearchConfig {
+        limits: SearchLimits { depth, budget },
+        ..SearchConfig::full(depth)
+    };
+    let mut searcher = Searcher::new(config, table);
+    if let Some(stop) = stop {
+        searcher = searcher.with_stop(stop);
     }
-    moves.remove(0)
+    searcher.search(&board, info)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::eval;
     use std::time;
     use std::str::FromStr;
 
@@ -363,7 +688,7 @@ mod tests {
     fn test_win_piece_cmp() {
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega(board, 6), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_nega(board, 6).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
         println!("{}", start.elapsed().as_secs_f32());
     }
 
@@ -380,14 +705,14 @@ mod tests {
     fn test_win_piece_cmp_3() {
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega_iterative(board, 6), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_nega_iterative(board, 6).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
         println!("{}", start.elapsed().as_secs_f32());
     }
     #[test]
     fn test_win_piece_cmp_4() {
         let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega_iterative_transposition(board, 6), ChessMove::from_san(&board, "Qb7+").unwrap());
+        assert_eq!(find_best_move_nega_iterative_transposition(board, 6).unwrap(), ChessMove::from_san(&board, "Qb7+").unwrap());
         println!("{}", start.elapsed().as_secs_f32());
     }
 
@@ -395,7 +720,7 @@ mod tests {
     fn test_win_piece_cmp_6() {
         let board = Board::from_str("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
         let start = time::Instant::now();
-        find_best_move_nega_transposition(board, 6);
+        find_best_move_nega_transposition(board, 6).unwrap();
         println!("{}", start.elapsed().as_secs_f32());
     }
 
@@ -403,7 +728,7 @@ mod tests {
     fn test_mate_in_1() {
         let board = Board::from_str("1n2kbnr/3ppppp/2b1r3/8/3qP3/1Q3PP1/3N3P/R1B1KBNR w Kk - 0 3").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega_iterative_transposition(board, 6), ChessMove::from_san(&board, "Qxb8#").unwrap());
+        assert_eq!(find_best_move_nega_iterative_transposition(board, 6).unwrap(), ChessMove::from_san(&board, "Qxb8#").unwrap());
         println!("{}", start.elapsed().as_secs_f32());
     }
 
@@ -411,7 +736,7 @@ mod tests {
     fn test_mate_in_1_black() {
         let board = Board::from_str("2k3r1/5r2/8/8/8/8/8/7K b - - 0 1").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega_iterative_transposition(board, 6), ChessMove::from_san(&board, "Rh7#").unwrap());
+        assert_eq!(find_best_move_nega_iterative_transposition(board, 6).unwrap(), ChessMove::from_san(&board, "Rh7#").unwrap());
         println!("{}", start.elapsed().as_secs_f32());
     }
 
@@ -419,14 +744,213 @@ mod tests {
     fn test_eval_speed() {
         let board = Board::from_str("2k3r1/5r2/8/8/8/8/8/7K b - - 0 1").unwrap();
         let start = time::Instant::now();
-        assert_eq!(find_best_move_nega_iterative_transposition(board, 6), ChessMove::from_san(&board, "Rh7#").unwrap());
+        assert_eq!(find_best_move_nega_iterative_transposition(board, 6).unwrap(), ChessMove::from_san(&board, "Rh7#").unwrap());
         println!("{}", start.elapsed().as_secs_f32());
     }
 
+    #[test]
+    fn test_timed() {
+        let board = Board::default();
+        let start = time::Instant::now();
+        let mut depths = vec![];
+        let m = find_best_move_nega_timed(board, 100, Some(time::Duration::from_millis(200)), None, &TranspositionTable::new(DEFAULT_HASH_MB), |info| depths.push(info.depth)).best_move.unwrap();
+        assert!(board.legal(m));
+        assert!(start.elapsed() < time::Duration::from_millis(400));
+        assert_eq!(depths[0], 2);
+    }
+
+    #[test]
+    fn test_timed_mate_in_1() {
+        let board = Board::from_str("2k3r1/5r2/8/8/8/8/8/7K b - - 0 1").unwrap();
+        assert_eq!(find_best_move_nega_timed(board, 100, Some(time::Duration::from_millis(200)), None, &TranspositionTable::new(DEFAULT_HASH_MB), |_| ()).best_move, Some(ChessMove::from_san(&board, "Rh7#").unwrap()));
+    }
+
+    #[test]
+    fn test_fastest_mate() {
+        // Rh2 mates at once, most other rook moves still mate within a few moves
+        let board = Board::from_str("7k/8/8/8/8/8/5R2/1K4R1 w - - 0 1").unwrap();
+        let result = find_best_move_nega_timed(board, 6, None, None, &TranspositionTable::new(DEFAULT_HASH_MB), |_| ());
+        assert_eq!(result.best_move, Some(ChessMove::from_san(&board, "Rh2").unwrap()));
+        assert_eq!(result.score, MATE - 1);
+        let board = Board::from_str("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 0").unwrap();
+        let result = find_best_move_nega_timed(board, 4, None, None, &TranspositionTable::new(DEFAULT_HASH_MB), |_| ());
+        assert_eq!(result.best_move, Some(ChessMove::from_san(&board, "Nf6+").unwrap()));
+        assert_eq!(mate_in_moves(result.score), Some(2));
+    }
+
+    #[test]
+    fn test_mate_in_moves() {
+        assert_eq!(mate_in_moves(MATE - 1), Some(1));
+        assert_eq!(mate_in_moves(MATE - 5), Some(3));
+        assert_eq!(mate_in_moves(mated_in(0)), Some(0));
+        assert_eq!(mate_in_moves(mated_in(4)), Some(-2));
+        assert_eq!(mate_in_moves(900), None);
+    }
+
+    #[test]
+    fn test_table_kept_between_searches() {
+        let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        let mut first_nodes = 0;
+        let first = find_best_move_nega_timed(board, 6, None, None, &table, |info| first_nodes = info.stats.nodes).best_move.unwrap();
+        let mut second_nodes = 0;
+        let second = find_best_move_nega_timed(board, 6, None, None, &table, |info| second_nodes = info.stats.nodes).best_move.unwrap();
+        assert_eq!(first, second);
+        assert!(second_nodes * 2 < first_nodes);
+        // after the move is played the reply is still found faster than with an empty table
+        let board = board.make_move_new(first);
+        let mut warm_nodes = 0;
+        find_best_move_nega_timed(board, 4, None, None, &table, |info| warm_nodes = info.stats.nodes);
+        let mut cold_nodes = 0;
+        find_best_move_nega_timed(board, 4, None, None, &TranspositionTable::new(DEFAULT_HASH_MB), |info| cold_nodes = info.stats.nodes);
+        assert!(warm_nodes < cold_nodes);
+    }
+
+    #[test]
+    fn test_pv() {
+        let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        let mut iterations = 0;
+        let result = find_best_move_nega_timed(board, 6, None, None, &table, |info| {
+            iterations += 1;
+            assert_eq!(Some(info.pv[0]), info.best_move);
+            assert!(info.pv.len() <= info.depth as usize + MAX_EXTENSIONS as usize);
+        });
+        assert_eq!(iterations, 3);
+        assert_eq!(result.depth, 6);
+        assert_eq!(result.best_move, Some(ChessMove::from_san(&board, "Qb7+").unwrap()));
+        assert_eq!(Some(result.pv[0]), result.best_move);
+        assert!(result.pv.len() > 1);
+        // the line has to be playable
+        let mut b = board;
+        for &m in &result.pv {
+            assert!(b.legal(m));
+            b = b.make_move_new(m);
+        }
+    }
+
+    #[test]
+    fn test_pv_mate() {
+        let board = Board::from_str("2k3r1/5r2/8/8/8/8/8/7K b - - 0 1").unwrap();
+        let result = find_best_move_nega_timed(board, 4, None, None, &TranspositionTable::new(DEFAULT_HASH_MB), |_| ());
+        assert_eq!(result.pv, vec![ChessMove::from_san(&board, "Rh7#").unwrap()]);
+    }
+
+    #[test]
+    fn test_quiescence() {
+        // the pawn on d5 is defended, taking it right before the horizon loses the queen
+        let board = Board::from_str("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_ne!(find_best_move_nega(board, 1).unwrap(), ChessMove::from_san(&board, "Qxd5").unwrap());
+        assert_eq!(quiescence(&board, 0, -i32::MAX, i32::MAX, 1), eval::evaluate(&board));
+        // a hanging queen is counted as won
+        let board = Board::from_str("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let won = board.make_move_new(ChessMove::from_san(&board, "Rxd5").unwrap());
+        assert_eq!(quiescence(&board, 0, -i32::MAX, i32::MAX, 1), eval::evaluate(&won));
+        // a queen promotion too
+        let board = Board::from_str("8/1P5k/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promoted = board.make_move_new(ChessMove::new(Square::B7, Square::B8, Some(Piece::Queen)));
+        assert_eq!(quiescence(&board, 0, -i32::MAX, i32::MAX, 1), eval::evaluate(&promoted));
+        // and a pawn taken en passant
+        let board = Board::from_str("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let taken = board.make_move_new(ChessMove::new(Square::E5, Square::D6, None));
+        assert_eq!(quiescence(&board, 0, -i32::MAX, i32::MAX, 1), eval::evaluate(&taken));
+    }
+
+    #[test]
+    fn test_quiescence_checks() {
+        let board = Board::from_str("7k/8/8/8/8/8/5R2/1K4R1 w - - 0 1").unwrap();
+        let table = TranspositionTable::new(0);
+        let mut search = SearchContext::new(&table, None, None);
+        assert_eq!(quiescence_context(&board, 0, 0, -i32::MAX, i32::MAX, 1, &mut search), eval::evaluate(&board));
+        search.quiescence_checks = true;
+        assert_eq!(quiescence_context(&board, 0, 0, -i32::MAX, i32::MAX, 1, &mut search), MATE - 1);
+    }
+
+    #[test]
+    fn test_root_window() {
+        let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
+        let moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
+        let search = |alpha, beta| {
+            let table = TranspositionTable::new(DEFAULT_HASH_MB);
+            let mut search = SearchContext::new(&table, None, None);
+            find_best_move_nega_moves_transposition_ordering_context(&board, 4, &moves, alpha, beta, &mut search)[0]
+        };
+        let (best_move, score) = search(-i32::MAX, i32::MAX);
+        // a window around the score finds the same move and score
+        assert_eq!(search(score - 10, score + 10), (best_move, score));
+        // a window above the score fails low, one below it fails high
+        assert!(search(score + 10, score + 20).1 <= score + 10);
+        assert!(search(score - 20, score - 10).1 >= score - 10);
+        // the aspiration search widens the window until the score fits
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        let mut search = SearchContext::new(&table, None, None);
+        assert_eq!(aspiration_search(&board, 4, &moves, score + 500, &mut search)[0], (best_move, score));
+        assert_eq!(aspiration_search(&board, 4, &moves, score - 500, &mut search)[0], (best_move, score));
+    }
+
+    #[test]
+    fn test_extensions() {
+        let search = |board: &Board, extensions| {
+            let moves = MoveGen::new_legal(board).collect::<Vec<ChessMove>>();
+            let table = TranspositionTable::new(DEFAULT_HASH_MB);
+            let mut search = SearchContext::new(&table, None, None);
+            search.pruning.extensions = extensions;
+            find_best_move_nega_moves_transposition_ordering_context(board, 2, &moves, -i32::MAX, i32::MAX, &mut search)[0]
+        };
+        // mates in three plies seen at depth 2, the checks and forced replies don't count
+        for (fen, san) in &[
+            ("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 0", "Nf6+"),
+            ("7k/1p4p1/p4b1p/3N3P/2p5/2rb4/PP2r3/K2R2R1 b - - 0 1", "Rc1+"),
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            assert_eq!(search(&board, true), (ChessMove::from_san(&board, san).unwrap(), MATE - 3));
+        }
+        // Kb6 leaves black a single move, after it the quiet Rh8 mates
+        let board = Board::from_str("k7/7R/8/1K6/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(search(&board, true), (ChessMove::from_san(&board, "Kb6").unwrap(), MATE - 3));
+        assert!(!is_mate_score(search(&board, false).1));
+    }
+
+    #[test]
+    fn test_extension_cap() {
+        // black can check forever, the extensions have to stop at some point
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/q7/2Q1K3 b - - 0 1").unwrap();
+        let result = find_best_move_nega_timed(board, 4, None, None, &TranspositionTable::new(DEFAULT_HASH_MB), |_| ());
+        assert_eq!(result.depth, 4);
+    }
+
+    #[test]
+    fn test_null_move_allowed() {
+        assert!(null_move_allowed(&Board::default()));
+        // in check
+        assert!(!null_move_allowed(&Board::from_str("4k3/8/8/8/8/8/4r3/R3K3 w - - 0 1").unwrap()));
+        // only pawns, or a single minor piece left for the side to move
+        assert!(!null_move_allowed(&Board::from_str("4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1").unwrap()));
+        assert!(!null_move_allowed(&Board::from_str("4k3/4p3/8/8/8/8/4P3/2B1K3 w - - 0 1").unwrap()));
+        assert!(null_move_allowed(&Board::from_str("4k3/4p3/8/8/8/8/4P3/R3K3 w - - 0 1").unwrap()));
+        // what the other side has doesn't matter
+        assert!(!null_move_allowed(&Board::from_str("r3k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1").unwrap()));
+    }
+
+    #[test]
+    fn test_null_move_zugzwang() {
+        // a king and pawn ending is all about zugzwang, the safeguards must keep null moves out of it entirely
+        let board = Board::from_str("8/8/4k3/8/4K3/4P3/8/8 w - - 0 1").unwrap();
+        let moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
+        let search = |pruning| {
+            let table = TranspositionTable::new(DEFAULT_HASH_MB);
+            let mut search = SearchContext::new(&table, None, None);
+            search.pruning = pruning;
+            let best = find_best_move_nega_moves_transposition_ordering_context(&board, 6, &moves, -i32::MAX, i32::MAX, &mut search)[0];
+            (best, search.stats.nodes)
+        };
+        assert_eq!(search(PruningConfig { null_move: true, ..PruningConfig::none() }), search(PruningConfig::none()));
+    }
+
     #[test]
     fn test_ordering() {
         let board = Board::default();
-        let mut table = HashMap::new();
-        negamax_transposition_ordering(&board, 2, -i32::MAX, i32::MAX, 1, &mut table);
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        negamax_transposition_ordering(&board, 2, 0, -i32::MAX, i32::MAX, 1, &table);
     }
 }
\ No newline at end of file
@@ -0,0 +1,369 @@
+use chess::*;
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::eval::move_changes;
+use super::evaluator::Evaluator;
+
+// one input for every piece type of either side on every square, seen from one side
+const INPUTS: usize = 2 * 6 * 64;
+// hidden activations are clipped to 0..=QA, output weights are scaled by QB
+const QA: i32 = 255;
+const QB: i32 = 64;
+// centipawns per unit of the network's output
+const SCALE: i32 = 400;
+const MAGIC: &[u8; 4] = b"AMNN";
+const VERSION: u32 = 1;
+// keeps a corrupt header from asking for gigabytes
+const MAX_HIDDEN: usize = 4096;
+
+/// An efficiently updatable neural network: one hidden layer with clipped ReLU activations, seen from both sides.
+///
+/// The inputs are the 768 combinations of own or enemy piece, piece type and square, from the point of view of
+/// one side. For black the board is mirrored vertically, so both sides see their own pieces starting on the
+/// first ranks. Input `(enemy * 6 + piece) * 64 + square` is 1 if that piece stands there, with `enemy` 0 for the
+/// side's own pieces and 1 for the other side's, `piece` 0 for pawns up to 5 for kings and `square` 0 for a1 up to
+/// 63 for h8 after mirroring. Every side has an accumulator, the hidden biases plus the weights of its active inputs.
+/// The output is the dot product of the clipped accumulators of the side to move and the other side
+/// with the output weights, plus the output bias, and becomes centipawns for the side to move
+/// as `output * 400 / (255 * 64)`.
+///
+/// The weights file is little endian and holds, without padding:
+///
+/// - the 4 bytes `AMNN`
+/// - the format version, a u32, currently 1
+/// - the hidden size `H`, a u32
+/// - the input weights, 768 × `H` i16s, the `H` weights of input 0 first
+/// - the hidden biases, `H` i16s
+/// - the output weights, 2 × `H` i16s, first the ones for the side to move, then the ones for the other side
+/// - the output bias, an i32
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    hidden: usize,
+    input_weights: Vec<i16>,
+    hidden_bias: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+// reads the file front to back
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < n {
+            return Err("the file ends too early".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(self.u32()? as i32)
+    }
+
+    fn i16s(&mut self, n: usize) -> Result<Vec<i16>, String> {
+        Ok(self.take(2 * n)?.chunks(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect())
+    }
+}
+
+impl Network {
+    /// Reads a network in the format described above.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err("not a network file".to_string());
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("unknown version {}", version));
+        }
+        let hidden = reader.u32()? as usize;
+        if hidden == 0 || hidden > MAX_HIDDEN {
+            return Err(format!("hidden size {} out of range", hidden));
+        }
+        let network = Network {
+            hidden,
+            input_weights: reader.i16s(INPUTS * hidden)?,
+            hidden_bias: reader.i16s(hidden)?,
+            output_weights: reader.i16s(2 * hidden)?,
+            output_bias: reader.i32()?,
+        };
+        if !reader.bytes.is_empty() {
+            return Err("unexpected data after the output bias".to_string());
+        }
+        Ok(network)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        Network::from_bytes(&bytes).map_err(|e| format!("{}: {}", path.as_ref().display(), e))
+    }
+
+    /// The network in the format `from_bytes` reads.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.hidden as u32).to_le_bytes());
+        for w in self.input_weights.iter().chain(&self.hidden_bias).chain(&self.output_weights) {
+            bytes.extend_from_slice(&w.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    /// A tiny network that comes with the engine. It isn't trained, its weights are set by hand to count material,
+    /// centralised pieces, advanced pawns and a castled king, which is enough to play legal chess and to test the code.
+    /// Each of these terms has two hidden neurons, one for the side's own pieces and one for the enemy's.
+    pub fn tiny() -> Self {
+        const HIDDEN: usize = 8;
+        const MATERIAL: [i16; 6] = [4, 12, 13, 20, 36, 0];
+        const OUTPUT: [i16; HIDDEN] = [510, -510, 34, -34, 30, -30, 41, -41];
+        let mut input_weights = vec![0; INPUTS * HIDDEN];
+        for enemy in 0..2 {
+            for &piece in ALL_PIECES.iter() {
+                for sq in 0..64 {
+                    let (file, rank) = (sq % 8, sq / 8);
+                    let weights = &mut input_weights[((enemy * 6 + piece.to_index()) * 64 + sq) * HIDDEN..][..HIDDEN];
+                    weights[enemy] = MATERIAL[piece.to_index()];
+                    let central = match piece {
+                        Piece::Pawn => (3..=4).contains(&file) && (3..=4).contains(&rank),
+                        Piece::Knight | Piece::Bishop | Piece::Queen => (2..=5).contains(&file) && (2..=5).contains(&rank),
+                        _ => false,
+                    };
+                    if central {
+                        weights[2 + enemy] = 6;
+                    }
+                    if piece == Piece::Pawn && rank >= 2 {
+                        weights[4 + enemy] = (rank as i16 - 1) * 2;
+                    }
+                    // on the first rank and not in the middle
+                    if piece == Piece::King && rank == 0 && !(3..=5).contains(&file) {
+                        weights[6 + enemy] = 10;
+                    }
+                }
+            }
+        }
+        Network {
+            hidden: HIDDEN,
+            input_weights,
+            hidden_bias: vec![0; HIDDEN],
+            // the other side's neurons count the other way round
+            output_weights: OUTPUT.iter().copied().chain(OUTPUT.iter().map(|w| -w)).collect(),
+            output_bias: 0,
+        }
+    }
+
+    pub fn hidden(&self) -> usize {
+        self.hidden
+    }
+
+    /// Evaluation of the position `accumulator` belongs to from the point of view of `side_to_move`.
+    pub fn evaluate(&self, accumulator: &Accumulator, side_to_move: Color) -> i32 {
+        let (us, them) = match side_to_move {
+            Color::White => (&accumulator.white, &accumulator.black),
+            Color::Black => (&accumulator.black, &accumulator.white),
+        };
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+        let activate = |values: &[i32], weights: &[i16]| -> i64 {
+            values.iter().zip(weights).map(|(&v, &w)| v.clamp(0, QA) as i64 * w as i64).sum()
+        };
+        let output = activate(us, our_weights) + activate(them, their_weights) + self.output_bias as i64;
+        (output * SCALE as i64 / (QA * QB) as i64) as i32
+    }
+
+    fn weights(&self, input: usize) -> &[i16] {
+        &self.input_weights[input * self.hidden..(input + 1) * self.hidden]
+    }
+}
+
+// the input a piece activates for `perspective`
+fn input(perspective: Color, piece: Piece, color: Color, sq: Square) -> usize {
+    let enemy = if color == perspective { 0 } else { 1 };
+    let sq = if perspective == Color::White { sq.to_index() } else { sq.to_index() ^ 56 };
+    (enemy * 6 + piece.to_index()) * 64 + sq
+}
+
+/// The hidden layer of a position before the activation, for both sides. Wider than the weights,
+/// so adding up many of them can't overflow.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Accumulator {
+    white: Vec<i32>,
+    black: Vec<i32>,
+}
+
+// clone_from copies into the vectors that are already there, that's how the search updates its accumulators
+impl Clone for Accumulator {
+    fn clone(&self) -> Self {
+        Accumulator {
+            white: self.white.clone(),
+            black: self.black.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.white.clone_from(&source.white);
+        self.black.clone_from(&source.black);
+    }
+}
+
+impl Accumulator {
+    /// The accumulator of `board` computed from scratch.
+    pub fn new(net: &Network, board: &Board) -> Self {
+        let bias = net.hidden_bias.iter().map(|&b| b as i32).collect::<Vec<i32>>();
+        let mut accumulator = Accumulator {
+            white: bias.clone(),
+            black: bias,
+        };
+        for sq in *board.combined() {
+            accumulator.update(net, board.piece_on(sq).unwrap(), board.color_on(sq).unwrap(), sq, 1);
+        }
+        accumulator
+    }
+
+    fn update(&mut self, net: &Network, piece: Piece, color: Color, sq: Square, sign: i32) {
+        for (perspective, values) in [(Color::White, &mut self.white), (Color::Black, &mut self.black)].iter_mut() {
+            for (v, &w) in values.iter_mut().zip(net.weights(input(*perspective, piece, color, sq))) {
+                *v += sign * w as i32;
+            }
+        }
+    }
+
+    /// Sets `child` to the accumulator after `m` is played on `board`, only the inputs the move changes are updated.
+    /// Nothing is allocated if `child` already has the network's hidden size.
+    pub fn make_move(&self, net: &Network, board: &Board, m: ChessMove, child: &mut Accumulator) {
+        child.clone_from(self);
+        move_changes(board, m, |piece, color, sq, added| child.update(net, piece, color, sq, if added { 1 } else { -1 }));
+    }
+}
+
+/// Evaluates with a `Network`, keeping its accumulator up to date from move to move.
+#[derive(Clone)]
+pub struct Nnue {
+    net: Arc<Network>,
+}
+
+impl Nnue {
+    pub fn new(net: Network) -> Self {
+        Nnue { net: Arc::new(net) }
+    }
+}
+
+impl Evaluator for Nnue {
+    type State = Accumulator;
+
+    fn state(&self, board: &Board) -> Accumulator {
+        Accumulator::new(&self.net, board)
+    }
+
+    fn make_move(&self, state: &Accumulator, board: &Board, m: ChessMove, child: &mut Accumulator) {
+        state.make_move(&self.net, board, m, child);
+    }
+
+    fn evaluate(&mut self, board: &Board, state: &Accumulator) -> i32 {
+        let eval = self.net.evaluate(state, board.side_to_move());
+        if board.side_to_move() == Color::White { eval } else { -eval }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_load() {
+        let net = Network::tiny();
+        assert_eq!(net.hidden(), 8);
+        let tiny = net.to_bytes();
+        assert_eq!(tiny.len(), 4 + 4 + 4 + 2 * (768 * 8 + 8 + 2 * 8) + 4);
+        assert_eq!(Network::from_bytes(&tiny), Ok(net));
+        assert!(Network::from_bytes(&tiny[..tiny.len() - 1]).unwrap_err().contains("ends too early"));
+        let mut bytes = tiny.clone();
+        bytes.push(0);
+        assert!(Network::from_bytes(&bytes).is_err());
+        bytes[0] = b'X';
+        assert_eq!(Network::from_bytes(&bytes), Err("not a network file".to_string()));
+        // a hidden size of 0
+        let mut bytes = tiny;
+        bytes[8..12].copy_from_slice(&0u32.to_le_bytes());
+        assert!(Network::from_bytes(&bytes).is_err());
+        assert!(Network::load("no such network.nnue").is_err());
+    }
+
+    #[test]
+    fn test_tiny_net() {
+        let mut nnue = Nnue::new(Network::tiny());
+        let mut evaluate = |fen: &str| {
+            let board = Board::from_str(fen).unwrap();
+            let state = nnue.state(&board);
+            nnue.evaluate(&board, &state)
+        };
+        assert_eq!(evaluate("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), 0);
+        assert_eq!(evaluate("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"), 0);
+        // white is a queen up, whoever is to move
+        let up = evaluate("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert!(up > 800);
+        assert_eq!(evaluate("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"), up);
+        // the same position with the colours swapped
+        assert_eq!(evaluate("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR b KQkq - 0 1"), -up);
+    }
+
+    // plays the first, the last and a middle move of every position down to `depth` plies and checks that
+    // the accumulator follows the moves
+    fn check_incremental(nnue: &mut Nnue, board: &Board, accumulator: &Accumulator, depth: usize) {
+        assert_eq!(*accumulator, nnue.state(board));
+        assert_eq!(nnue.evaluate(board, accumulator), nnue.evaluate(board, &nnue.state(board)));
+        if depth == 0 {
+            return;
+        }
+        let moves = MoveGen::new_legal(board).collect::<Vec<ChessMove>>();
+        if moves.is_empty() {
+            return;
+        }
+        // one accumulator for all children, as in the search
+        let mut incremental = accumulator.clone();
+        for &m in &[moves[0], moves[moves.len() / 2], moves[moves.len() - 1]] {
+            let child = board.make_move_new(m);
+            nnue.make_move(accumulator, board, m, &mut incremental);
+            check_incremental(nnue, &child, &incremental, depth - 1);
+        }
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut nnue = Nnue::new(Network::tiny());
+        let positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            // castling on both sides, captures
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            // en passant
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            // promotions, with and without a capture
+            "1r2k3/P7/8/8/8/8/6p1/4K2R b K - 0 1",
+        ];
+        for fen in positions.iter() {
+            let board = Board::from_str(fen).unwrap();
+            let accumulator = nnue.state(&board);
+            check_incremental(&mut nnue, &board, &accumulator, 4);
+        }
+        // every move of the promotion position, the first, middle and last move alone miss some
+        let board = Board::from_str(positions[3]).unwrap();
+        let accumulator = nnue.state(&board);
+        let mut incremental = accumulator.clone();
+        for m in MoveGen::new_legal(&board) {
+            nnue.make_move(&accumulator, &board, m, &mut incremental);
+            assert_eq!(incremental, nnue.state(&board.make_move_new(m)));
+        }
+    }
+}
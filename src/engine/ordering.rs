@@ -0,0 +1,287 @@
+use chess::*;
+
+use std::cmp::max;
+
+/// Material value of a piece as used for ordering and exchanges. The king is never captured,
+/// it only gets a large value so it is the last piece an exchange is continued with.
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Queen => 950,
+        Piece::Rook => 560,
+        Piece::Bishop => 330,
+        Piece::Knight => 300,
+        Piece::Pawn => 100,
+        Piece::King => 20_000,
+    }
+}
+
+// the piece a move takes, en passant captures land on an empty square
+fn captured_piece(board: &Board, m: ChessMove) -> Option<Piece> {
+    match board.piece_on(m.get_dest()) {
+        Some(piece) => Some(piece),
+        None if is_en_passant(board, m) => Some(Piece::Pawn),
+        None => None,
+    }
+}
+
+fn is_en_passant(board: &Board, m: ChessMove) -> bool {
+    board.piece_on(m.get_source()) == Some(Piece::Pawn)
+        && m.get_source().get_file() != m.get_dest().get_file()
+        && board.piece_on(m.get_dest()).is_none()
+}
+
+/// Most valuable victim, least valuable attacker. Higher keys are tried first.
+pub fn mvv_lva(board: &Board, m: ChessMove) -> i32 {
+    let victim = captured_piece(board, m).map_or(0, piece_value);
+    10 * victim - piece_value(board.piece_on(m.get_source()).unwrap())
+}
+
+// every piece of either color attacking sq, with sliders seeing through the squares missing from occupied
+fn attackers(board: &Board, sq: Square, occupied: BitBoard) -> BitBoard {
+    let pawns = board.pieces(Piece::Pawn);
+    let bishops = board.pieces(Piece::Bishop) | board.pieces(Piece::Queen);
+    let rooks = board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    // a pawn of one color attacks sq from the squares a pawn of the other color on sq would attack
+    let attackers = (get_pawn_attacks(sq, Color::White, !EMPTY) & pawns & board.color_combined(Color::Black))
+        | (get_pawn_attacks(sq, Color::Black, !EMPTY) & pawns & board.color_combined(Color::White))
+        | (get_knight_moves(sq) & board.pieces(Piece::Knight))
+        | (get_king_moves(sq) & board.pieces(Piece::King))
+        | (get_bishop_moves(sq, occupied) & bishops)
+        | (get_rook_moves(sq, occupied) & rooks);
+    attackers & occupied
+}
+
+/// Static exchange evaluation: the material the side to move wins or loses if both sides keep capturing
+/// on the destination square of `m` with their least valuable piece, each side free to stop when
+/// continuing would lose more. Pins and checks are ignored. Quiet moves score 0 unless the piece hangs.
+pub fn see(board: &Board, m: ChessMove) -> i32 {
+    let sq = m.get_dest();
+    let mut occupied = *board.combined() ^ BitBoard::from_square(m.get_source());
+    if is_en_passant(board, m) {
+        // the captured pawn stands next to the destination, not on it
+        occupied ^= BitBoard::from_square(Square::make_square(m.get_source().get_rank(), m.get_dest().get_file()));
+    }
+    let mut piece = board.piece_on(m.get_source()).unwrap();
+    // gain[d] is what the side making the d-th capture gets if the exchange stopped right after it
+    let mut gain = [0; 32];
+    gain[0] = captured_piece(board, m).map_or(0, piece_value);
+    if let Some(promotion) = m.get_promotion() {
+        gain[0] += piece_value(promotion) - piece_value(Piece::Pawn);
+        piece = promotion;
+    }
+    let mut side = !board.side_to_move();
+    let mut d = 0;
+    loop {
+        d += 1;
+        gain[d] = piece_value(piece) - gain[d - 1];
+        if d == gain.len() - 1 {
+            break;
+        }
+        let ours = attackers(board, sq, occupied) & board.color_combined(side);
+        let next = [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King]
+            .iter()
+            .map(|&p| (p, ours & board.pieces(p)))
+            .find(|(_, b)| *b != EMPTY);
+        let (next_piece, from) = match next {
+            Some(next) => next,
+            None => break,
+        };
+        // the king can't capture into a square the other side still attacks
+        if next_piece == Piece::King && attackers(board, sq, occupied) & board.color_combined(!side) != EMPTY {
+            break;
+        }
+        occupied ^= BitBoard::from_square(from.to_square());
+        piece = next_piece;
+        side = !side;
+    }
+    while d > 1 {
+        d -= 1;
+        gain[d - 1] = -max(-gain[d - 1], gain[d]);
+    }
+    gain[0]
+}
+
+/// A move that neither captures nor promotes.
+pub fn is_quiet(board: &Board, m: ChessMove) -> bool {
+    captured_piece(board, m).is_none() && m.get_promotion().is_none()
+}
+
+// history scores are halved once one of them reaches this, so old cutoffs slowly lose their weight
+const HISTORY_LIMIT: i32 = 1 << 20;
+
+/// What a search learned about quiet moves from the cutoffs it has seen so far.
+///
+/// Killer moves are the last two quiet moves that caused a cutoff at a ply, a sibling node is likely
+/// refuted by the same move. The history table, indexed by `[color][from][to]`, adds up the cutoffs
+/// of each quiet move over the whole tree, weighted by depth.
+pub struct MoveHistory {
+    killers: Vec<[Option<ChessMove>; 2]>,
+    history: Vec<[[i32; 64]; 64]>,
+}
+
+impl MoveHistory {
+    pub fn new() -> Self {
+        // both tables are only allocated once the first cutoff is recorded
+        MoveHistory {
+            killers: vec![],
+            history: vec![],
+        }
+    }
+
+    pub fn killers(&self, ply: usize) -> [Option<ChessMove>; 2] {
+        self.killers.get(ply).copied().unwrap_or([None; 2])
+    }
+
+    pub fn history(&self, color: Color, m: ChessMove) -> i32 {
+        self.history.get(color.to_index()).map_or(0, |h| h[m.get_source().to_index()][m.get_dest().to_index()])
+    }
+
+    /// Records that the quiet move `m` caused a cutoff at `ply` in a search of `depth` plies.
+    pub fn update(&mut self, color: Color, ply: usize, depth: i8, m: ChessMove) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+        if self.history.is_empty() {
+            self.history = vec![[[0; 64]; 64]; NUM_COLORS];
+        }
+        let score = &mut self.history[color.to_index()][m.get_source().to_index()][m.get_dest().to_index()];
+        *score += depth as i32 * depth as i32;
+        if *score >= HISTORY_LIMIT {
+            for score in self.history.iter_mut().flatten().flatten() {
+                *score /= 2;
+            }
+        }
+    }
+}
+
+/// Orders the legal moves of a node the way the search wants to try them: the hash move, captures and queen
+/// promotions that don't lose material by MVV-LVA, the killer moves of `ply`, the other quiet moves by history score,
+/// then captures and promotions that lose material, the worst last.
+pub fn order_moves(board: &Board, mut moves: MoveGen, hash_move: Option<ChessMove>, ply: usize, history: &MoveHistory) -> Vec<ChessMove> {
+    let mut ordered = Vec::with_capacity(moves.len());
+    // the hash move may come from another position with the same index in the table
+    let hash_move = hash_move.filter(|&m| board.legal(m));
+    ordered.extend(hash_move);
+    let enemies = *board.color_combined(!board.side_to_move());
+    let ep = board.en_passant().map_or(EMPTY, |sq| {
+        BitBoard::from_square(sq.ubackward(!board.side_to_move()))
+    });
+    moves.set_iterator_mask(enemies | ep);
+    let mut captures = (&mut moves).filter(|&m| Some(m) != hash_move).map(|m| (m, see(board, m))).collect::<Vec<(ChessMove, i32)>>();
+    moves.set_iterator_mask(!EMPTY);
+    // queen promotions are searched along with the captures, like in quiescence search
+    let (promotions, quiet): (Vec<ChessMove>, Vec<ChessMove>) = moves.filter(|&m| Some(m) != hash_move)
+        .partition(|m| m.get_promotion() == Some(Piece::Queen));
+    captures.extend(promotions.into_iter().map(|m| (m, see(board, m))));
+    captures.sort_by_key(|&(m, see)| if see >= 0 { (0, -mvv_lva(board, m)) } else { (1, -see) });
+    let losing = captures.iter().position(|&(_, see)| see < 0).unwrap_or(captures.len());
+    ordered.extend(captures[..losing].iter().map(|c| c.0));
+    // killers come from sibling positions, so they may not even be legal here
+    let killers = history.killers(ply).iter().flatten().copied()
+        .filter(|&k| Some(k) != hash_move && is_quiet(board, k) && board.legal(k))
+        .collect::<Vec<ChessMove>>();
+    ordered.extend(&killers);
+    let mut quiet = quiet.into_iter().filter(|m| !killers.contains(m)).collect::<Vec<ChessMove>>();
+    let color = board.side_to_move();
+    quiet.sort_by_key(|&m| -history.history(color, m));
+    ordered.extend(quiet);
+    ordered.extend(captures[losing..].iter().map(|c| c.0));
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn see_san(fen: &str, san: &str) -> i32 {
+        let board = Board::from_str(fen).unwrap();
+        see(&board, ChessMove::from_san(&board, san).unwrap())
+    }
+
+    #[test]
+    fn test_see() {
+        // undefended pawn
+        assert_eq!(see_san("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "Rxe5"), 100);
+        // pawn defended by a pawn, the rook is lost
+        assert_eq!(see_san("1k6/8/3p4/4p3/8/8/8/1K2R3 w - - 0 1", "Rxe5"), 100 - 560);
+        // knight takes a pawn defended by a knight, backed up by a bishop and a rook behind the queen
+        assert_eq!(see_san("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "Nxe5"), 100 - 300);
+        // the rook behind the queen joins in once the queen has captured
+        assert_eq!(see_san("1k6/8/8/3p4/8/8/3Q4/K2R4 w - - 0 1", "Qxd5"), 100);
+        assert_eq!(see_san("1k6/8/4p3/3p4/8/8/3Q4/K2R4 w - - 0 1", "Qxd5"), 100 - 950 + 100);
+    }
+
+    #[test]
+    fn test_see_special() {
+        // en passant
+        let board = Board::from_str("1k6/8/8/3pP3/8/8/8/1K6 w - d6 0 1").unwrap();
+        assert_eq!(see(&board, ChessMove::new(Square::E5, Square::D6, None)), 100);
+        // promotion on a defended square
+        let board = Board::from_str("2kr4/4P3/8/8/8/8/8/1K6 w - - 0 1").unwrap();
+        assert_eq!(see(&board, ChessMove::new(Square::E7, Square::D8, Some(Piece::Queen))), 560 + 950 - 100 - 950);
+        let board = Board::from_str("1k6/4P3/8/8/8/8/8/1K6 w - - 0 1").unwrap();
+        assert_eq!(see(&board, ChessMove::new(Square::E7, Square::E8, Some(Piece::Queen))), 850);
+        // the king can only recapture if nothing else covers the square
+        assert_eq!(see_san("3k4/R7/8/8/8/8/8/1K6 w - - 0 1", "Rd7+"), -560);
+        assert_eq!(see_san("3k4/R6R/8/8/8/8/8/1K6 w - - 0 1", "Rad7+"), 0);
+    }
+
+    #[test]
+    fn test_order_moves() {
+        let board = Board::from_str("1k6/8/4p3/3p4/5q2/8/4N3/1K1R4 w - - 0 1").unwrap();
+        let hash_move = ChessMove::from_san(&board, "Kc2").unwrap();
+        let moves = order_moves(&board, MoveGen::new_legal(&board), Some(hash_move), 0, &MoveHistory::new());
+        assert_eq!(moves.len(), MoveGen::new_legal(&board).len());
+        assert_eq!(moves[0], hash_move);
+        assert_eq!(moves[1], ChessMove::from_san(&board, "Nxf4").unwrap());
+        // the pawn is defended, taking it loses the rook
+        assert_eq!(*moves.last().unwrap(), ChessMove::from_san(&board, "Rxd5").unwrap());
+        // a hash move from another position is ignored
+        let other = ChessMove::from_san(&Board::default(), "e4").unwrap();
+        assert!(!order_moves(&board, MoveGen::new_legal(&board), Some(other), 0, &MoveHistory::new()).contains(&other));
+    }
+
+    #[test]
+    fn test_order_promotions() {
+        let board = Board::from_str("1k6/4P3/8/8/5q2/8/4N3/1K1R4 w - - 0 1").unwrap();
+        let san = |s| ChessMove::from_san(&board, s).unwrap();
+        let mut history = MoveHistory::new();
+        history.update(Color::White, 0, 4, san("Rc1"));
+        let moves = order_moves(&board, MoveGen::new_legal(&board), None, 0, &history);
+        // the queen promotion comes right after the capture, before the killer and the underpromotions
+        let promotion = |piece| ChessMove::new(Square::E7, Square::E8, Some(piece));
+        assert_eq!(moves[..3], [san("Nxf4"), promotion(Piece::Queen), san("Rc1")]);
+        assert!(moves.iter().position(|&m| m == promotion(Piece::Knight)).unwrap() > 2);
+    }
+
+    #[test]
+    fn test_killers_history() {
+        let board = Board::from_str("1k6/8/4p3/3p4/5q2/8/4N3/1K1R4 w - - 0 1").unwrap();
+        let san = |s| ChessMove::from_san(&board, s).unwrap();
+        let mut history = MoveHistory::new();
+        history.update(Color::White, 3, 4, san("Rc1"));
+        history.update(Color::White, 3, 2, san("Ka1"));
+        history.update(Color::White, 5, 6, san("Ng1"));
+        history.update(Color::White, 5, 6, san("Ng1"));
+        assert_eq!(history.killers(3), [Some(san("Ka1")), Some(san("Rc1"))]);
+        assert_eq!(history.killers(5), [Some(san("Ng1")), None]);
+        assert_eq!(history.killers(4), [None, None]);
+        assert_eq!(history.history(Color::White, san("Ng1")), 72);
+        assert_eq!(history.history(Color::Black, san("Ng1")), 0);
+        let moves = order_moves(&board, MoveGen::new_legal(&board), None, 3, &history);
+        // the winning capture, the killers of the ply, then the quiet move with the best history
+        assert_eq!(moves[..4], [san("Nxf4"), san("Ka1"), san("Rc1"), san("Ng1")]);
+        // a killer that isn't legal here is skipped
+        let other = Board::from_str("1k6/8/8/8/8/8/8/1K1R4 w - - 0 1").unwrap();
+        history.update(Color::White, 3, 1, ChessMove::from_san(&other, "Rd8+").unwrap());
+        let moves = order_moves(&board, MoveGen::new_legal(&board), None, 3, &history);
+        assert_eq!(moves.len(), MoveGen::new_legal(&board).len());
+        assert_eq!(moves[..2], [san("Nxf4"), san("Ka1")]);
+    }
+}
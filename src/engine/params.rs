@@ -0,0 +1,381 @@
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::iter;
+use std::path::Path;
+
+use super::eval::{score, Score};
+
+// Default piece-square tables, from white's point of view with a8 first so they read like a board.
+// Black uses the same tables mirrored vertically. They are the tables of Ronald Friederich's PeSTO as published on
+// the Chess Programming Wiki (https://www.chessprogramming.org/PeSTO%27s_Evaluation_Function), which were tuned
+// together with its material values and game phase weights, so those are the defaults too.
+#[rustfmt::skip]
+const MG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     98, 134,  61,  95,  68, 126,  34, -11,
+     -6,   7,  26,  31,  65,  56,  25, -20,
+    -14,  13,   6,  21,  23,  12,  17, -23,
+    -27,  -2,  -5,  12,  17,   6,  10, -25,
+    -26,  -4,  -4, -10,   3,   3,  33, -12,
+    -35,  -1, -20, -23, -15,  24,  38, -22,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const EG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const MG_KNIGHT: [i32; 64] = [
+   -167, -89, -34, -49,  61, -97, -15,-107,
+    -73, -41,  72,  36,  23,  62,   7, -17,
+    -47,  60,  37,  65,  84, 129,  73,  44,
+     -9,  17,  19,  53,  37,  69,  18,  22,
+    -13,   4,  16,  13,  28,  19,  21,  -8,
+    -23,  -9,  12,  10,  19,  17,  25, -16,
+    -29, -53, -12,  -3,  -1,  18, -14, -19,
+   -105, -21, -58, -33, -17, -28, -19, -23,
+];
+#[rustfmt::skip]
+const EG_KNIGHT: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+#[rustfmt::skip]
+const MG_BISHOP: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+#[rustfmt::skip]
+const EG_BISHOP: [i32; 64] = [
+    -14, -21, -11,  -8,  -7,  -9, -17, -24,
+     -8,  -4,   7, -12,  -3, -13,  -4, -14,
+      2,  -8,   0,  -1,  -2,   6,   0,   4,
+     -3,   9,  12,   9,  14,  10,   3,   2,
+     -6,   3,  13,  19,   7,  10,  -3,  -9,
+    -12,  -3,   8,  10,  13,   3,  -7, -15,
+    -14, -18,  -7,  -1,   4,  -9, -15, -27,
+    -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+];
+#[rustfmt::skip]
+const MG_ROOK: [i32; 64] = [
+     32,  42,  32,  51,  63,   9,  31,  43,
+     27,  32,  58,  62,  80,  67,  26,  44,
+     -5,  19,  26,  36,  17,  45,  61,  16,
+    -24, -11,   7,  26,  24,  35,  -8, -20,
+    -36, -26, -12,  -1,   9,  -7,   6, -23,
+    -45, -25, -16, -17,   3,   0,  -5, -33,
+    -44, -16, -20,  -9,  -1,  11,  -6, -71,
+    -19, -13,   1,  17,  16,   7, -37, -26,
+];
+#[rustfmt::skip]
+const EG_ROOK: [i32; 64] = [
+     13,  10,  18,  15,  12,  12,   8,   5,
+     11,  13,  13,  11,  -3,   3,   8,   3,
+      7,   7,   7,   5,   4,  -3,  -5,  -3,
+      4,   3,  13,   1,   2,   1,  -1,   2,
+      3,   5,   8,   4,  -5,  -6,  -8, -11,
+     -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+     -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+     -9,   2,   3,  -1,  -5, -13,   4, -20,
+];
+#[rustfmt::skip]
+const MG_QUEEN: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+#[rustfmt::skip]
+const EG_QUEEN: [i32; 64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+#[rustfmt::skip]
+const MG_KING: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+#[rustfmt::skip]
+const EG_KING: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43,
+];
+
+// a default table as a board of middlegame and endgame pairs
+fn pst(mg: &[i32; 64], eg: &[i32; 64]) -> [[Score; 8]; 8] {
+    let mut table = [[Score::default(); 8]; 8];
+    for (i, (&mg, &eg)) in mg.iter().zip(eg.iter()).enumerate() {
+        table[i / 8][i % 8] = score(mg, eg);
+    }
+    table
+}
+
+/// Weights of the evaluation terms, as middlegame and endgame pairs.
+/// The terms count things on the board, their weight is what one of them is worth, so a penalty has negative weights.
+///
+/// Saved as JSON to files ending in `.json` and as TOML to any other file, weights missing from a file keep their default.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    /// for every pawn, knight, bishop, rook and queen
+    pub material: [Score; 5],
+    /// for every piece by the square it stands on, one table for each piece from the pawn to the king.
+    /// They are written from white's point of view with the eighth rank first, black's are mirrored
+    pub pst: [[[Score; 8]; 8]; 6],
+    /// how much every piece from the pawn to the king counts towards the game phase, which blends
+    /// the middlegame and the endgame weights. It falls from `max_phase` as pieces come off
+    pub phase: [i32; 6],
+    /// for two or more bishops
+    pub bishop_pair: Score,
+    /// for every pawn with another pawn of its own in front of it on the same file
+    pub doubled: Score,
+    /// for every pawn without a pawn of its own on either neighbouring file
+    pub isolated: Score,
+    /// for every pawn whose neighbours have all advanced past it while an enemy pawn guards the square in front of it
+    pub backward: Score,
+    /// for every pawn defended by a pawn or standing next to one, by rank counted from its own side
+    pub connected: [Score; 8],
+    /// for every pawn without an enemy pawn in front of it on its own or a neighbouring file, by rank
+    pub passed: [Score; 8],
+    /// for every pawn of its own on the two ranks in front of the king, on its file or a neighbouring one
+    pub pawn_shield: Score,
+    /// for every file next to or under the king without a pawn of its own on it
+    pub king_open_file: Score,
+    /// for every attack of an enemy piece on the king or the squares around it
+    pub king_zone_attack: Score,
+    /// for every square a knight, bishop, rook or queen can move to that isn't guarded by an enemy pawn
+    pub mobility: [Score; 4],
+    /// for every rook on a file without pawns
+    pub rook_open_file: Score,
+    /// for every rook on a file with only enemy pawns
+    pub rook_semi_open_file: Score,
+    /// for every rook on the seventh rank counted from its own side
+    pub rook_seventh: Score,
+    /// for every knight on the fourth to sixth rank defended by a pawn that no enemy pawn can ever attack
+    pub knight_outpost: Score,
+    /// for every pawn of its own on the colour of a bishop's squares
+    pub bad_bishop: Score,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            material: [score(82, 94), score(337, 281), score(365, 297), score(477, 512), score(1025, 936)],
+            pst: [
+                pst(&MG_PAWN, &EG_PAWN),
+                pst(&MG_KNIGHT, &EG_KNIGHT),
+                pst(&MG_BISHOP, &EG_BISHOP),
+                pst(&MG_ROOK, &EG_ROOK),
+                pst(&MG_QUEEN, &EG_QUEEN),
+                pst(&MG_KING, &EG_KING),
+            ],
+            // the phase is at 0 with only kings and pawns left
+            phase: [0, 1, 1, 2, 4, 0],
+            bishop_pair: score(50, 50),
+            doubled: score(-10, -20),
+            isolated: score(-10, -15),
+            backward: score(-8, -10),
+            connected: [score(0, 0), score(5, 2), score(7, 4), score(10, 6), score(15, 12), score(25, 20), score(40, 30), score(0, 0)],
+            // a passed pawn is worth most in the endgame, when there are no pieces left to stop it
+            passed: [score(0, 0), score(5, 10), score(8, 15), score(12, 25), score(25, 45), score(45, 80), score(70, 130), score(0, 0)],
+            pawn_shield: score(10, 0),
+            king_open_file: score(-20, 0),
+            king_zone_attack: score(-8, 0),
+            mobility: [score(4, 4), score(5, 5), score(2, 4), score(1, 2)],
+            rook_open_file: score(25, 10),
+            rook_semi_open_file: score(10, 5),
+            rook_seventh: score(20, 30),
+            knight_outpost: score(20, 10),
+            bad_bishop: score(-3, -5),
+        }
+    }
+}
+
+impl EvalParams {
+    /// The game phase with all pieces of the starting position on the board, the evaluation is all middlegame there.
+    pub fn max_phase(&self) -> i32 {
+        [16, 4, 4, 4, 2, 2].iter().zip(self.phase.iter()).map(|(count, phase)| count * phase).sum()
+    }
+
+    /// Reads weights written by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let params = if is_json(path) {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&text).map_err(|e| e.to_string())
+        };
+        params.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let text = if is_json(path) {
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())?
+        } else {
+            toml::to_string(self).map_err(|e| e.to_string())?
+        };
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Every single weight, middlegame and endgame ones apart, always in the same order. This is what the tuner changes,
+    /// everything but the phase weights.
+    pub fn weights_mut(&mut self) -> Vec<&mut i32> {
+        self.scores_mut().flat_map(|Score { mg, eg }| iter::once(mg).chain(iter::once(eg))).collect()
+    }
+
+    /// Weight `i` of `weights_mut`, found without collecting all the others.
+    pub fn weight_mut(&mut self, i: usize) -> &mut i32 {
+        let score = self.scores_mut().nth(i / 2).expect("no such weight");
+        if i.is_multiple_of(2) { &mut score.mg } else { &mut score.eg }
+    }
+
+    // the scores the weights of `weights_mut` belong to, in their order
+    fn scores_mut(&mut self) -> impl Iterator<Item = &mut Score> {
+        let pst = self.pst.iter_mut().enumerate().flat_map(|(piece, table)| {
+            // pawns never stand on the first or the last rank
+            let ranks = if piece == 0 { &mut table[1..7] } else { &mut table[..] };
+            ranks.iter_mut().flat_map(|rank| rank.iter_mut())
+        });
+        self.material.iter_mut()
+            .chain(IntoIterator::into_iter([&mut self.bishop_pair, &mut self.doubled, &mut self.isolated, &mut self.backward]))
+            .chain(self.connected.iter_mut())
+            .chain(self.passed.iter_mut())
+            .chain(IntoIterator::into_iter([&mut self.pawn_shield, &mut self.king_open_file, &mut self.king_zone_attack]))
+            .chain(self.mobility.iter_mut())
+            .chain(IntoIterator::into_iter([
+                &mut self.rook_open_file,
+                &mut self.rook_semi_open_file,
+                &mut self.rook_seventh,
+                &mut self.knight_outpost,
+                &mut self.bad_bishop,
+            ]))
+            .chain(pst)
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir().join("amar_chess_test_params.toml");
+        let mut params = EvalParams::default();
+        params.material[4] = score(1000, 980);
+        params.passed[6].eg = 200;
+        params.save(&path).unwrap();
+        assert_eq!(EvalParams::load(&path).unwrap(), params);
+        // a file with only some of the weights
+        std::fs::write(&path, "bishop_pair = [40, 60]\n").unwrap();
+        let loaded = EvalParams::load(&path).unwrap();
+        assert_eq!(loaded.bishop_pair, score(40, 60));
+        assert_eq!(loaded.material, EvalParams::default().material);
+        std::fs::remove_file(&path).unwrap();
+        assert!(EvalParams::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_save_load_json() {
+        let path = std::env::temp_dir().join("amar_chess_test_params.json");
+        let mut params = EvalParams::default();
+        params.mobility[1] = score(7, 3);
+        params.save(&path).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("\"mobility\": ["));
+        assert_eq!(EvalParams::load(&path).unwrap(), params);
+        std::fs::write(&path, "{ \"rook_seventh\": [15, 35] }").unwrap();
+        let loaded = EvalParams::load(&path).unwrap();
+        assert_eq!(loaded.rook_seventh, score(15, 35));
+        assert_eq!(loaded.passed, EvalParams::default().passed);
+        // TOML in a file named like JSON
+        std::fs::write(&path, "rook_seventh = [15, 35]\n").unwrap();
+        assert!(EvalParams::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_save_load_pst() {
+        let path = std::env::temp_dir().join("amar_chess_test_params_pst.toml");
+        let mut params = EvalParams::default();
+        // a knight on f3
+        params.pst[1][5][5] = score(45, -12);
+        params.phase[4] = 3;
+        params.save(&path).unwrap();
+        let loaded = EvalParams::load(&path).unwrap();
+        assert_eq!(loaded, params);
+        assert_eq!(loaded.max_phase(), 22);
+        let json = path.with_extension("json");
+        params.save(&json).unwrap();
+        assert_eq!(EvalParams::load(&json).unwrap(), params);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&json).unwrap();
+    }
+
+    #[test]
+    fn test_weights() {
+        let mut params = EvalParams::default();
+        let count = params.weights_mut().len();
+        // every weight is changed exactly once
+        for (i, w) in params.weights_mut().into_iter().enumerate() {
+            *w = i as i32;
+        }
+        assert_eq!(params.material[0], score(0, 1));
+        // the piece-square tables come last, the king's h1 entry is the very last
+        assert_eq!(params.pst[5][7][7], score(count as i32 - 2, count as i32 - 1));
+        assert_eq!(params.pst[0][0][0], EvalParams::default().pst[0][0][0]);
+        assert_eq!(count, 2 * (37 + 48 + 5 * 64));
+        // one at a time they are the same weights
+        let mut one_by_one = EvalParams::default();
+        for i in 0..count {
+            *one_by_one.weight_mut(i) = i as i32;
+        }
+        assert_eq!(one_by_one, params);
+    }
+}
@@ -0,0 +1,178 @@
+use chess::*;
+
+use super::eval::Score;
+use super::params::EvalParams;
+
+// the squares on ranks in front of `rank` as seen from `color`
+pub fn ranks_ahead(color: Color, rank: Rank) -> BitBoard {
+    let rank = rank.to_index();
+    match color {
+        Color::White if rank == 7 => EMPTY,
+        Color::White => BitBoard::new(!0 << (8 * (rank + 1))),
+        Color::Black => BitBoard::new((1 << (8 * rank)) - 1),
+    }
+}
+
+pub fn relative_rank(color: Color, sq: Square) -> usize {
+    match color {
+        Color::White => sq.get_rank().to_index(),
+        Color::Black => 7 - sq.get_rank().to_index(),
+    }
+}
+
+/// The pawn structure of one side from its own point of view.
+pub fn side_structure(board: &Board, color: Color, params: &EvalParams) -> Score {
+    let pawns = board.pieces(Piece::Pawn);
+    let own = pawns & board.color_combined(color);
+    let enemy = pawns & board.color_combined(!color);
+    let mut total = Score::default();
+    for sq in own {
+        let file = get_file(sq.get_file());
+        let neighbours = get_adjacent_files(sq.get_file());
+        let ahead = ranks_ahead(color, sq.get_rank());
+        let rank = relative_rank(color, sq);
+        let doubled = own & file & ahead != EMPTY;
+        if doubled {
+            total += params.doubled;
+        }
+        if own & neighbours == EMPTY {
+            total += params.isolated;
+        } else if own & neighbours & !ahead == EMPTY {
+            // a pawn on the last rank would have promoted, so there always is a square in front of it
+            let stop = if color == Color::White { sq.uup() } else { sq.udown() };
+            if get_pawn_attacks(stop, color, enemy) != EMPTY {
+                total += params.backward;
+            }
+        }
+        let supported = get_pawn_attacks(sq, !color, own) != EMPTY;
+        let phalanx = own & neighbours & get_rank(sq.get_rank()) != EMPTY;
+        if supported || phalanx {
+            total += params.connected[rank];
+        }
+        // only the front pawn of a doubled pair counts as passed, the other one is blocked by it
+        if !doubled && enemy & (file | neighbours) & ahead == EMPTY {
+            total += params.passed[rank];
+        }
+    }
+    total
+}
+
+/// Scores doubled, isolated, backward, connected and passed pawns of both sides.
+pub fn pawn_structure(board: &Board, params: &EvalParams) -> Score {
+    let mut total = side_structure(board, Color::White, params);
+    total -= side_structure(board, Color::Black, params);
+    total
+}
+
+// a splitmix64 step, spreads the bits of x over the whole hash
+fn mix(x: u64) -> u64 {
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Hash of the pawns of both sides and nothing else. `Board::get_pawn_hash` would be the obvious choice,
+/// but in the version of the chess crate used here it always returns 0.
+pub fn pawn_hash(board: &Board) -> u64 {
+    let pawns = board.pieces(Piece::Pawn);
+    let white = (pawns & board.color_combined(Color::White)).0;
+    let black = (pawns & board.color_combined(Color::Black)).0;
+    mix(white ^ mix(black.wrapping_add(0x9e3779b97f4a7c15)))
+}
+
+// entries of a pawn table, the same pawns come up again and again in a search so a small table is plenty
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+/// Pawn structure scores of the pawn formations seen so far. Pawns move rarely, so most positions
+/// of a search share their pawns with one evaluated before and their structure is looked up instead of scored again.
+/// The scores are only valid for the weights they were computed with, a search never changes them.
+pub struct PawnTable {
+    table: CacheTable<Score>,
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        PawnTable {
+            table: CacheTable::new(PAWN_TABLE_SIZE, Score::default()),
+        }
+    }
+
+    /// The same as `pawn_structure(board, params)`.
+    pub fn get(&mut self, board: &Board, params: &EvalParams) -> Score {
+        let hash = pawn_hash(board);
+        if let Some(score) = self.table.get(hash) {
+            return score;
+        }
+        let score = pawn_structure(board, params);
+        self.table.add(hash, score);
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn structure(fen: &str) -> Score {
+        pawn_structure(&Board::from_str(fen).unwrap(), &EvalParams::default())
+    }
+
+    fn sum(scores: &[Score]) -> Score {
+        let mut total = Score::default();
+        for &s in scores {
+            total += s;
+        }
+        total
+    }
+
+    #[test]
+    fn test_doubled_isolated() {
+        let p = EvalParams::default();
+        // e3 is passed, e2 is stuck behind it, both are isolated
+        assert_eq!(structure("4k3/8/8/8/8/4P3/4P3/4K3 w - - 0 1"), sum(&[p.doubled, p.isolated, p.isolated, p.passed[2]]));
+        // the same for black from the other side
+        assert_eq!(structure("4k3/4p3/4p3/8/8/8/8/4K3 w - - 0 1"), -sum(&[p.doubled, p.isolated, p.isolated, p.passed[2]]));
+    }
+
+    #[test]
+    fn test_connected_passed() {
+        let p = EvalParams::default();
+        // d5 and e5 stand next to each other, f4 defends e5 and is defended by nothing
+        assert_eq!(structure("4k3/8/8/3PP3/5P2/8/8/4K3 w - - 0 1"), sum(&[p.connected[4], p.connected[4], p.passed[4], p.passed[4], p.passed[3]]));
+    }
+
+    #[test]
+    fn test_backward() {
+        let p = EvalParams::default();
+        // d3 is left behind by c4 and e4 and can't advance, c5 guards d4
+        let board = "4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1";
+        let white = side_structure(&Board::from_str(board).unwrap(), Color::White, &p);
+        // c4 and e4 are defended by d3 and e4 is passed
+        let mut expected = sum(&[p.backward, p.connected[3], p.connected[3], p.passed[3]]);
+        assert_eq!(white, expected);
+        // without the black pawn d4 is safe and d3 isn't backward, all three are passed
+        let white = side_structure(&Board::from_str("4k3/8/8/8/2P1P3/3P4/8/4K3 w - - 0 1").unwrap(), Color::White, &p);
+        expected -= p.backward;
+        expected += p.passed[3];
+        expected += p.passed[2];
+        assert_eq!(white, expected);
+    }
+
+    #[test]
+    fn test_pawn_table() {
+        let p = EvalParams::default();
+        let mut table = PawnTable::new();
+        let mut board = Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for _ in 0..2 {
+            assert_eq!(table.get(&board, &p), pawn_structure(&board, &p));
+        }
+        // a knight move keeps the pawns and their hash, a pawn move changes it
+        let knight = board.make_move_new(ChessMove::from_san(&board, "Nd3").unwrap());
+        assert_eq!(pawn_hash(&knight), pawn_hash(&board));
+        assert_eq!(table.get(&knight, &p), pawn_structure(&knight, &p));
+        board = board.make_move_new(ChessMove::from_san(&board, "a3").unwrap());
+        assert_ne!(pawn_hash(&board), pawn_hash(&knight));
+        assert_eq!(table.get(&board, &p), pawn_structure(&board, &p));
+    }
+}
@@ -0,0 +1,361 @@
+use chess::*;
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use super::evaluator::{Classical, Evaluator};
+use super::negamax::*;
+use super::params::EvalParams;
+use super::stats::{IterationStats, SearchStats};
+use super::tt::{Bound, TranspositionTable};
+
+/// When a search has to stop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchLimits {
+    /// depth of the last iteration, or the only depth searched without iterative deepening.
+    /// The root is always searched at least a ply deep
+    pub depth: i8,
+    /// how long the search may take, it stops in the middle of an iteration once this is used up
+    pub budget: Option<Duration>,
+}
+
+/// What a `Searcher` does. Every search the engine has is one of these configurations,
+/// from plain alpha-beta to everything switched on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchConfig {
+    /// look positions up in the transposition table and store the results in it
+    pub transposition: bool,
+    /// search the hash move, good captures, killers and moves with a good history first, all but the first move
+    /// of a node with a null window, and every iteration with an aspiration window around the last score
+    pub ordering: bool,
+    /// search depth 2, 4, ... up to the limit, and the limit itself if it is odd,
+    /// every iteration starting with the best moves of the one before
+    pub iterative_deepening: bool,
+    /// print the statistics of every iteration, they are collected either way
+    pub count_nodes: bool,
+    /// search captures at the leaves instead of evaluating them in the middle of an exchange
+    pub quiescence: bool,
+    /// also search quiet moves that give check on the first ply of the quiescence search
+    pub quiescence_checks: bool,
+    pub pruning: PruningConfig,
+    pub limits: SearchLimits,
+    /// threads searching at the same time, see `Searcher::search`
+    pub threads: usize,
+    /// weights of the classical evaluation a searcher made with `Searcher::new` uses
+    pub params: EvalParams,
+}
+
+impl SearchConfig {
+    /// Alpha-beta to a fixed depth with quiescence search at the leaves and nothing else.
+    pub fn plain(depth: i8) -> Self {
+        SearchConfig {
+            transposition: false,
+            ordering: false,
+            iterative_deepening: false,
+            count_nodes: false,
+            quiescence: true,
+            quiescence_checks: false,
+            pruning: PruningConfig::none(),
+            limits: SearchLimits { depth, budget: None },
+            threads: 1,
+            params: EvalParams::default(),
+        }
+    }
+
+    /// Everything the engine has, on a single thread.
+    pub fn full(depth: i8) -> Self {
+        SearchConfig {
+            transposition: true,
+            ordering: true,
+            iterative_deepening: true,
+            pruning: PruningConfig::default(),
+            ..SearchConfig::plain(depth)
+        }
+    }
+}
+
+/// Searches positions the way its `SearchConfig` says, evaluating them with `E`.
+pub struct Searcher<'a, E: Evaluator = Classical> {
+    pub config: SearchConfig,
+    /// only used if the config asks for it, a table of size 0 is enough otherwise
+    table: &'a TranspositionTable,
+    stop: Option<Arc<AtomicBool>>,
+    // every search thread gets a clone of its own
+    evaluator: E,
+}
+
+impl<'a> Searcher<'a> {
+    /// A searcher with the classical evaluation and the weights of the config.
+    pub fn new(config: SearchConfig, table: &'a TranspositionTable) -> Self {
+        Searcher::with_evaluator(config, table, Classical::new(config.params))
+    }
+}
+
+impl<'a, E: Evaluator> Searcher<'a, E> {
+    /// A searcher evaluating positions with `evaluator`, the weights of the config are left alone.
+    pub fn with_evaluator(config: SearchConfig, table: &'a TranspositionTable, evaluator: E) -> Self {
+        Searcher {
+            config,
+            table,
+            stop: None,
+            evaluator,
+        }
+    }
+
+    /// Raising `stop` ends the search as soon as possible, it still returns the best move of the last completed iteration.
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Bookkeeping for one thread of a search with the features of the config switched on.
+    pub fn context(&self, deadline: Option<Instant>, stop: Option<Arc<AtomicBool>>) -> SearchContext<'a, E> {
+        let mut search = SearchContext::with_evaluator(self.evaluator.clone(), self.table, deadline, stop);
+        search.transposition = self.config.transposition;
+        search.ordering = self.config.ordering;
+        search.use_history = self.config.ordering;
+        search.quiescence = self.config.quiescence;
+        search.quiescence_checks = self.config.quiescence_checks;
+        search.pruning = self.config.pruning;
+        search
+    }
+
+    /// Searches `board` until the depth limit is reached, the budget runs out or the stop flag is raised.
+    /// `info` is called after every completed iteration.
+    ///
+    /// With more than one thread the search is a lazy SMP search: helper threads on the rayon pool search the
+    /// same position at the same time, each with its own move history, and share what they find through the table.
+    /// Only the main thread reports and picks the move, the helpers just fill the table with results it can use.
+    pub fn search<F: FnMut(&SearchResult) + Send>(&self, board: &Board, info: F) -> SearchResult {
+        let start = Instant::now();
+        let deadline = self.config.limits.budget.map(|b| start + b);
+        self.table.new_search();
+        let mut search = self.context(deadline, self.stop.clone());
+        let depth = self.config.limits.depth.max(1);
+        let first_depth = if self.config.iterative_deepening { depth.min(2) } else { depth };
+        if self.config.threads <= 1 {
+            return self.deepen(board, first_depth, depth, start, &mut search, info);
+        }
+        // raised by the main thread once it is done, the helpers' results are of no use after that
+        let done = Arc::new(AtomicBool::new(false));
+        let helper_stats = Mutex::new(SearchStats::default());
+        let mut result = rayon::scope(|s| {
+            for id in 1..self.config.threads {
+                let done = Arc::clone(&done);
+                let helper_stats = &helper_stats;
+                s.spawn(move |_| {
+                    // a helper that only gets a thread once the main thread is done has nothing left to do
+                    if done.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let mut search = self.context(deadline, Some(done));
+                    // every other helper searches the odd depths, so the main thread finds entries a ply deeper than its own
+                    self.deepen(board, first_depth + (id % 2) as i8, depth + 1, start, &mut search, |_| ());
+                    helper_stats.lock().unwrap().merge(&search.stats);
+                });
+            }
+            let result = self.deepen(board, first_depth, depth, start, &mut search, info);
+            done.store(true, Ordering::Relaxed);
+            result
+        });
+        result.stats.merge(&helper_stats.into_inner().unwrap());
+        result
+    }
+
+    /// Searches `moves` of `board` once to `depth` with the full window, returns them sorted best first
+    /// together with their scores. Only the score of the first move is exact, the others are bounds.
+    pub fn search_moves(&self, board: &Board, depth: i8, moves: &[ChessMove]) -> Vec<(ChessMove, i32)> {
+        let mut search = self.context(None, self.stop.clone());
+        let move_values = find_best_move_nega_moves_transposition_ordering_context(board, depth.max(1), moves, -i32::MAX, i32::MAX, &mut search);
+        if self.config.count_nodes {
+            println!("{}", search.stats);
+        }
+        move_values
+    }
+
+    // searches from `first_depth` up to `depth` two plies at a time, the last step is a single ply if that ends on `depth`
+    fn deepen<F: FnMut(&SearchResult)>(&self, board: &Board, first_depth: i8, depth: i8, start: Instant, search: &mut SearchContext<E>, mut info: F) -> SearchResult {
+        let mut moves = MoveGen::new_legal(board).collect::<Vec<ChessMove>>();
+        // nothing to search, the game is already over
+        if moves.is_empty() {
+            search.stats.time = start.elapsed();
+            return SearchResult {
+                best_move: None,
+                score: if board.checkers().popcnt() == 0 { 0 } else { mated_in(0) },
+                pv: vec![],
+                depth: 0,
+                stats: search.stats.clone(),
+            };
+        }
+        if self.config.ordering {
+            if let Some(hash_move) = self.table.probe(board.get_hash(), 0).and_then(|e| e.best_move) {
+                if let Some(i) = moves.iter().position(|&m| m == hash_move) {
+                    moves[..=i].rotate_right(1);
+                }
+            }
+        }
+        let mut result = SearchResult {
+            best_move: Some(moves[0]),
+            score: 0,
+            pv: vec![moves[0]],
+            depth: 0,
+            stats: SearchStats::default(),
+        };
+        let mut run_depth = first_depth;
+        while run_depth <= depth {
+            let iteration_start = Instant::now();
+            let nodes = search.stats.nodes;
+            let move_values = aspiration_search(board, run_depth, &moves, result.score, search);
+            if search.aborted {
+                break;
+            }
+            let iteration = IterationStats {
+                depth: run_depth,
+                nodes: search.stats.nodes - nodes,
+                time: iteration_start.elapsed(),
+            };
+            if self.config.count_nodes {
+                println!("{}", iteration);
+            }
+            search.stats.iterations.push(iteration);
+            search.stats.time = start.elapsed();
+            if self.config.transposition {
+                self.table.store(board.get_hash(), move_values[0].1, run_depth, 0, Bound::Exact, Some(move_values[0].0));
+            }
+            result = SearchResult {
+                best_move: Some(move_values[0].0),
+                score: move_values[0].1,
+                pv: search.pv().to_vec(),
+                depth: run_depth,
+                stats: search.stats.clone(),
+            };
+            info(&result);
+            moves = move_values.iter().map(|v| v.0).collect::<Vec<ChessMove>>();
+            // the next iteration takes several times as long as this one, so only start it if it has a chance to finish
+            if let Some(budget) = self.config.limits.budget {
+                if start.elapsed() * 2 > budget {
+                    break;
+                }
+            }
+            if run_depth >= depth {
+                break;
+            }
+            run_depth = (run_depth + 2).min(depth);
+        }
+        search.stats.time = start.elapsed();
+        result.stats = search.stats.clone();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tt::DEFAULT_HASH_MB;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_configs_agree() {
+        let board = Board::from_str("2k3r1/5r2/8/8/8/8/8/7K b - - 0 1").unwrap();
+        let mate = ChessMove::from_san(&board, "Rh7#").unwrap();
+        for i in 0..32 {
+            let config = SearchConfig {
+                transposition: i & 1 != 0,
+                ordering: i & 2 != 0,
+                iterative_deepening: i & 4 != 0,
+                quiescence: i & 8 != 0,
+                pruning: if i & 16 != 0 { PruningConfig::default() } else { PruningConfig::none() },
+                ..SearchConfig::plain(4)
+            };
+            let table = TranspositionTable::new(DEFAULT_HASH_MB);
+            let result = Searcher::new(config, &table).search(&board, |_| ());
+            assert_eq!((result.best_move, result.score, result.depth), (Some(mate), MATE - 1, 4), "{:?}", config);
+        }
+    }
+
+    #[test]
+    fn test_iterations() {
+        let board = Board::default();
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        let mut depths = vec![];
+        Searcher::new(SearchConfig::full(6), &table).search(&board, |info| depths.push(info.depth));
+        assert_eq!(depths, vec![2, 4, 6]);
+        let config = SearchConfig {
+            iterative_deepening: false,
+            ..SearchConfig::full(6)
+        };
+        let mut depths = vec![];
+        Searcher::new(config, &table).search(&board, |info| depths.push(info.depth));
+        assert_eq!(depths, vec![6]);
+        // an odd limit is searched too, not rounded to the next even depth
+        let mut depths = vec![];
+        Searcher::new(SearchConfig::full(5), &table).search(&board, |info| depths.push(info.depth));
+        assert_eq!(depths, vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn test_depth_zero() {
+        let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        // a depth of 0 or less still searches the root moves a ply deep
+        for &depth in &[0, -1] {
+            for &config in &[SearchConfig::plain(depth), SearchConfig::full(depth)] {
+                let result = Searcher::new(config, &table).search(&board, |_| ());
+                assert_eq!(result.depth, 1);
+                assert!(board.legal(result.best_move.unwrap()));
+            }
+        }
+        let moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
+        assert_eq!(Searcher::new(SearchConfig::plain(0), &table).search_moves(&board, 0, &moves).len(), moves.len());
+        assert!(find_best_move_nega(board, 0).is_some());
+        // below the root a depth of 0 or less is a leaf
+        assert_eq!(negamax(board, -1, 0, -i32::MAX, i32::MAX, 1), quiescence(&board, 0, -i32::MAX, i32::MAX, 1));
+    }
+
+    #[test]
+    fn test_stats() {
+        let board = Board::from_str("r7/5kp1/3p3p/2q2p2/p1P1pP2/4P1P1/1Q1N1K1P/8 w - - 0 2").unwrap();
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        let stats = Searcher::new(SearchConfig::full(6), &table).search(&board, |_| ()).stats;
+        assert_eq!(stats.iterations.iter().map(|i| i.depth).collect::<Vec<i8>>(), vec![2, 4, 6]);
+        assert_eq!(stats.iterations.iter().map(|i| i.nodes).sum::<u64>(), stats.nodes);
+        assert!(stats.qnodes > 0 && stats.qnodes < stats.nodes);
+        assert!(stats.tt_hits > 0 && stats.tt_hits <= stats.tt_probes);
+        assert!(stats.tt_cutoffs <= stats.tt_hits);
+        assert!(stats.first_move_cutoffs > 0 && stats.first_move_cutoffs <= stats.beta_cutoffs);
+        assert!(stats.branching_factor().unwrap() > 1.0);
+        // without a table nothing is probed
+        let stats = Searcher::new(SearchConfig::plain(4), &table).search(&board, |_| ()).stats;
+        assert_eq!(stats.tt_probes, 0);
+        assert!(stats.nodes > 0);
+    }
+
+    #[test]
+    fn test_stop() {
+        let board = Board::default();
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        let result = Searcher::new(SearchConfig::full(100), &table).with_stop(Arc::new(AtomicBool::new(true))).search(&board, |_| ());
+        // stopped before the first iteration is done, the move is still legal
+        assert_eq!(result.depth, 0);
+        assert!(board.legal(result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn test_game_over() {
+        let table = TranspositionTable::new(DEFAULT_HASH_MB);
+        for &(fen, score) in &[("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 0), ("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", mated_in(0))] {
+            let board = Board::from_str(fen).unwrap();
+            for &threads in &[1, 2] {
+                let config = SearchConfig {
+                    threads,
+                    ..SearchConfig::full(4)
+                };
+                let mut reports = 0;
+                let result = Searcher::new(config, &table).search(&board, |_| reports += 1);
+                assert_eq!((result.best_move, result.score, result.depth, reports), (None, score, 0, 0), "{}", fen);
+                assert!(result.pv.is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Nodes and time of one completed iteration of a search.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IterationStats {
+    pub depth: i8,
+    /// nodes visited by this iteration alone
+    pub nodes: u64,
+    /// time this iteration alone took
+    pub time: Duration,
+}
+
+impl fmt::Display for IterationStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "depth {} nodes {} time {}ms", self.depth, self.nodes, self.time.as_millis())
+    }
+}
+
+/// What a search did, collected by every search as it goes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    /// nodes visited, quiescence nodes included
+    pub nodes: u64,
+    /// nodes visited by the quiescence search
+    pub qnodes: u64,
+    /// transposition table lookups
+    pub tt_probes: u64,
+    /// lookups that found an entry of the position
+    pub tt_hits: u64,
+    /// lookups whose entry decided the node without searching it
+    pub tt_cutoffs: u64,
+    /// nodes where a move reached beta
+    pub beta_cutoffs: u64,
+    /// nodes where the first move searched already reached beta
+    pub first_move_cutoffs: u64,
+    /// time since the start of the search
+    pub time: Duration,
+    /// every completed iteration, shallowest first
+    pub iterations: Vec<IterationStats>,
+}
+
+fn ratio(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+impl SearchStats {
+    /// Nodes per second over the whole search.
+    pub fn nps(&self) -> u64 {
+        if self.time.as_micros() == 0 {
+            return 0;
+        }
+        (self.nodes as u128 * 1_000_000 / self.time.as_micros()) as u64
+    }
+
+    pub fn tt_hit_rate(&self) -> f64 {
+        ratio(self.tt_hits, self.tt_probes)
+    }
+
+    /// Share of the beta cutoffs that came from the first move, a measure of how good the move ordering is.
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        ratio(self.first_move_cutoffs, self.beta_cutoffs)
+    }
+
+    /// Effective branching factor of the last iteration: how many times more nodes one more ply of depth costs.
+    /// `None` until there are two iterations to compare.
+    pub fn branching_factor(&self) -> Option<f64> {
+        let (last, previous) = match self.iterations.as_slice() {
+            [.., previous, last] => (last, previous),
+            _ => return None,
+        };
+        if previous.nodes == 0 || last.depth <= previous.depth {
+            return None;
+        }
+        Some(ratio(last.nodes, previous.nodes).powf(1.0 / (last.depth - previous.depth) as f64))
+    }
+
+    /// Adds the counters of another thread of the same search. Its iterations aren't added,
+    /// they overlap with the ones of this thread.
+    pub fn merge(&mut self, other: &SearchStats) {
+        self.nodes += other.nodes;
+        self.qnodes += other.qnodes;
+        self.tt_probes += other.tt_probes;
+        self.tt_hits += other.tt_hits;
+        self.tt_cutoffs += other.tt_cutoffs;
+        self.beta_cutoffs += other.beta_cutoffs;
+        self.first_move_cutoffs += other.first_move_cutoffs;
+    }
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "nodes {} ({} quiescence) in {}ms, {} nps", self.nodes, self.qnodes, self.time.as_millis(), self.nps())?;
+        writeln!(f, "tt probes {} hits {:.1}% cutoffs {}", self.tt_probes, self.tt_hit_rate() * 100.0, self.tt_cutoffs)?;
+        write!(f, "beta cutoffs {} on the first move {:.1}%", self.beta_cutoffs, self.first_move_cutoff_rate() * 100.0)?;
+        if let Some(factor) = self.branching_factor() {
+            write!(f, "\nbranching factor {:.2}", factor)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iteration(depth: i8, nodes: u64) -> IterationStats {
+        IterationStats {
+            depth,
+            nodes,
+            time: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn test_rates() {
+        let stats = SearchStats {
+            nodes: 3000,
+            tt_probes: 200,
+            tt_hits: 50,
+            beta_cutoffs: 40,
+            first_move_cutoffs: 36,
+            time: Duration::from_millis(1500),
+            ..SearchStats::default()
+        };
+        assert_eq!(stats.nps(), 2000);
+        assert_eq!(stats.tt_hit_rate(), 0.25);
+        assert_eq!(stats.first_move_cutoff_rate(), 0.9);
+        // nothing searched yet
+        assert_eq!(SearchStats::default().nps(), 0);
+        assert_eq!(SearchStats::default().first_move_cutoff_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_branching_factor() {
+        let mut stats = SearchStats::default();
+        assert_eq!(stats.branching_factor(), None);
+        stats.iterations.push(iteration(2, 100));
+        assert_eq!(stats.branching_factor(), None);
+        // two plies deeper for 9 times the nodes is a factor of 3 per ply
+        stats.iterations.push(iteration(4, 900));
+        assert!((stats.branching_factor().unwrap() - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut stats = SearchStats {
+            nodes: 10,
+            qnodes: 4,
+            iterations: vec![iteration(2, 10)],
+            ..SearchStats::default()
+        };
+        let other = SearchStats {
+            nodes: 5,
+            qnodes: 1,
+            tt_probes: 3,
+            iterations: vec![iteration(3, 5)],
+            ..SearchStats::default()
+        };
+        stats.merge(&other);
+        assert_eq!((stats.nodes, stats.qnodes, stats.tt_probes), (15, 5, 3));
+        assert_eq!(stats.iterations, vec![iteration(2, 10)]);
+    }
+}
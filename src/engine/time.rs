@@ -0,0 +1,90 @@
+use chess::*;
+
+use std::cmp::min;
+use std::time::Duration;
+
+// time kept in reserve for communication with the gui on every move
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+// number of moves the remaining time is spread over when the gui doesn't send movestogo
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Clock information for a search, as sent by `go wtime ... btime ... winc ... binc ... movestogo ...`
+/// or `go movetime ...`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimeControl {
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub movetime: Option<Duration>,
+}
+
+impl TimeControl {
+    pub fn movetime(movetime: Duration) -> Self {
+        TimeControl {
+            movetime: Some(movetime),
+            ..TimeControl::default()
+        }
+    }
+
+    /// How long `side` may think about its next move, or `None` if the search isn't limited by time.
+    pub fn budget(&self, side: Color) -> Option<Duration> {
+        if let Some(movetime) = self.movetime {
+            return Some(movetime.saturating_sub(MOVE_OVERHEAD));
+        }
+        let (time, inc) = match side {
+            Color::White => (self.wtime?, self.winc.unwrap_or_default()),
+            Color::Black => (self.btime?, self.binc.unwrap_or_default()),
+        };
+        let moves_to_go = self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let budget = time / moves_to_go + inc * 3 / 4;
+        // never plan to use more than half of what is left on the clock
+        Some(min(budget, time / 2).saturating_sub(MOVE_OVERHEAD))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_movetime() {
+        let time = TimeControl::movetime(Duration::from_millis(1000));
+        assert_eq!(time.budget(Color::White), Some(Duration::from_millis(970)));
+        assert_eq!(time.budget(Color::Black), Some(Duration::from_millis(970)));
+    }
+
+    #[test]
+    fn test_clock() {
+        let time = TimeControl {
+            wtime: Some(Duration::from_millis(60_000)),
+            btime: Some(Duration::from_millis(3_000)),
+            winc: Some(Duration::from_millis(1_000)),
+            movestogo: Some(20),
+            ..TimeControl::default()
+        };
+        assert_eq!(time.budget(Color::White), Some(Duration::from_millis(3_000 + 750 - 30)));
+        assert_eq!(time.budget(Color::Black), Some(Duration::from_millis(150 - 30)));
+    }
+
+    #[test]
+    fn test_low_time() {
+        let time = TimeControl {
+            wtime: Some(Duration::from_millis(100)),
+            winc: Some(Duration::from_millis(1_000)),
+            ..TimeControl::default()
+        };
+        assert_eq!(time.budget(Color::White), Some(Duration::from_millis(50 - 30)));
+    }
+
+    #[test]
+    fn test_unlimited() {
+        assert_eq!(TimeControl::default().budget(Color::White), None);
+        let time = TimeControl {
+            wtime: Some(Duration::from_millis(1_000)),
+            ..TimeControl::default()
+        };
+        assert_eq!(time.budget(Color::Black), None);
+    }
+}
@@ -0,0 +1,331 @@
+use chess::*;
+
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use super::negamax::is_mate_score;
+
+/// Size of the table used when the caller doesn't pick one.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// What a stored score says about the real value of a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    /// the score is the value of the position
+    Exact,
+    /// the search failed high, the value is at least the score
+    Lower,
+    /// the search failed low, the value is at most the score
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub hash: u64,
+    pub score: i32,
+    pub depth: i8,
+    pub bound: Bound,
+    pub best_move: Option<ChessMove>,
+    /// search the entry was stored in, see `TranspositionTable::new_search`
+    pub generation: u8,
+}
+
+impl Entry {
+    /// Returns the stored score if it is deep enough and precise enough to decide a search
+    /// of `depth` plies with the window `alpha..beta` on its own.
+    pub fn cutoff(&self, depth: i8, alpha: i32, beta: i32) -> Option<i32> {
+        if self.depth < depth {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+}
+
+// An entry packed into 64 bits so a slot can be written with two atomic stores:
+// score in bits 0-23, depth in 24-31, bound in 32-33 (0 for an empty slot),
+// best move in 34-48 (from, to and promotion, 0 for none) and generation in 49-56.
+fn pack(score: i32, depth: i8, bound: Bound, best_move: Option<ChessMove>, generation: u8) -> u64 {
+    debug_assert!(score.abs() < 1 << 23);
+    let bound = match bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    let best_move = best_move.map_or(0, |m| {
+        m.get_source().to_index() as u64 | (m.get_dest().to_index() as u64) << 6 | (m.get_promotion().map_or(0, |p| p.to_index()) as u64) << 12
+    });
+    (score as u32 as u64 & 0xff_ffff) | (depth as u8 as u64) << 24 | bound << 32 | best_move << 34 | (generation as u64) << 49
+}
+
+fn unpack(hash: u64, data: u64) -> Option<Entry> {
+    let bound = match (data >> 32) & 3 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => return None,
+    };
+    let best_move = match (data >> 34) & 0x7fff {
+        0 => None,
+        m => Some(ChessMove::new(
+            ALL_SQUARES[(m & 63) as usize],
+            ALL_SQUARES[(m >> 6 & 63) as usize],
+            match m >> 12 {
+                0 => None,
+                p => Some(ALL_PIECES[p as usize]),
+            },
+        )),
+    };
+    Some(Entry {
+        hash,
+        // shifting the 24 bits to the top and back sign extends them
+        score: ((data << 40) as i64 >> 40) as i32,
+        depth: (data >> 24) as u8 as i8,
+        bound,
+        best_move,
+        generation: (data >> 49) as u8,
+    })
+}
+
+// The key is stored xored with the data. A slot that is read while another thread writes it
+// ends up with a key that matches no position, so a torn entry is never mistaken for a valid one.
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Fixed-size hash table of search results indexed by zobrist hash.
+///
+/// Each slot holds a single entry. A new entry replaces the old one unless the old one
+/// belongs to the same position, was searched deeper and comes from the current search.
+/// The table is meant to live for a whole game so later searches profit from earlier ones.
+///
+/// Slots are read and written with atomics instead of behind a lock, so the threads of a
+/// parallel search can all share one table.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let max_entries = (size_mb * 1024 * 1024 / size_of::<Slot>()).max(1);
+        // round down to a power of two so the index is a simple mask
+        let len = 1 << (63 - (max_entries as u64).leading_zeros());
+        TranspositionTable {
+            slots: (0..len).map(|_| Slot::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    fn slot(&self, hash: u64) -> &Slot {
+        &self.slots[hash as usize & (self.slots.len() - 1)]
+    }
+
+    fn load(&self, hash: u64) -> Option<Entry> {
+        let slot = self.slot(hash);
+        let data = slot.data.load(Ordering::Relaxed);
+        if slot.key.load(Ordering::Relaxed) ^ data != hash {
+            return None;
+        }
+        unpack(hash, data)
+    }
+
+    /// Looks up the entry of a position found `ply` plies from the root.
+    pub fn probe(&self, hash: u64, ply: usize) -> Option<Entry> {
+        self.load(hash).map(|e| Entry {
+            score: score_from_table(e.score, ply),
+            ..e
+        })
+    }
+
+    pub fn store(&self, hash: u64, score: i32, depth: i8, ply: usize, bound: Bound, best_move: Option<ChessMove>) {
+        let score = score_to_table(score, ply);
+        let generation = self.generation.load(Ordering::Relaxed);
+        if let Some(old) = self.load(hash) {
+            if old.depth > depth && old.generation == generation {
+                return;
+            }
+        }
+        let data = pack(score, depth, bound, best_move, generation);
+        let slot = self.slot(hash);
+        slot.data.store(data, Ordering::Relaxed);
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+    }
+
+    /// Marks the entries stored so far as belonging to an older search. They can still be probed,
+    /// but no longer keep newer results of the same position out of the table.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    /// Number of entries the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+// mate scores are stored as the distance from the position itself instead of from the root,
+// the same position can be reached at a different ply in another part of the tree
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if !is_mate_score(score) {
+        score
+    } else if score > 0 {
+        score + ply as i32
+    } else {
+        score - ply as i32
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if !is_mate_score(score) {
+        score
+    } else if score > 0 {
+        score - ply as i32
+    } else {
+        score + ply as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::negamax::{mated_in, MATE};
+
+    #[test]
+    fn test_size() {
+        let table = TranspositionTable::new(1);
+        assert!(table.capacity().is_power_of_two());
+        assert!(table.capacity() * size_of::<Slot>() <= 1024 * 1024);
+        assert!(table.capacity() * 2 * size_of::<Slot>() > 1024 * 1024);
+    }
+
+    #[test]
+    fn test_store_probe() {
+        let table = TranspositionTable::new(1);
+        let hash = Board::default().get_hash();
+        assert_eq!(table.probe(hash, 0), None);
+        table.store(hash, 30, 4, 0, Bound::Lower, None);
+        let entry = table.probe(hash, 0).unwrap();
+        assert_eq!((entry.score, entry.depth, entry.bound), (30, 4, Bound::Lower));
+        // another position mapping to the same slot isn't mistaken for this one
+        assert_eq!(table.probe(hash ^ (table.capacity() as u64), 0), None);
+        table.clear();
+        assert_eq!(table.probe(hash, 0), None);
+    }
+
+    #[test]
+    fn test_replacement() {
+        let table = TranspositionTable::new(1);
+        let hash = Board::default().get_hash();
+        table.store(hash, 30, 4, 0, Bound::Exact, None);
+        table.store(hash, 10, 2, 0, Bound::Exact, None);
+        assert_eq!(table.probe(hash, 0).unwrap().score, 30);
+        table.store(hash, 20, 6, 0, Bound::Upper, None);
+        assert_eq!(table.probe(hash, 0).unwrap().score, 20);
+        // a different position always takes the slot
+        let other = hash ^ (table.capacity() as u64);
+        table.store(other, 0, 1, 0, Bound::Exact, None);
+        assert_eq!(table.probe(hash, 0), None);
+        assert_eq!(table.probe(other, 0).unwrap().score, 0);
+    }
+
+    #[test]
+    fn test_new_search() {
+        let table = TranspositionTable::new(1);
+        let hash = Board::default().get_hash();
+        table.store(hash, 30, 6, 0, Bound::Exact, None);
+        table.new_search();
+        // old entries are still found
+        assert_eq!(table.probe(hash, 0).unwrap().score, 30);
+        // but a shallower result of the new search replaces them
+        table.store(hash, 10, 2, 0, Bound::Exact, None);
+        assert_eq!(table.probe(hash, 0).unwrap().score, 10);
+    }
+
+    #[test]
+    fn test_mate_scores() {
+        let table = TranspositionTable::new(1);
+        let hash = Board::default().get_hash();
+        // mate in 3 plies found 4 plies from the root is a mate in 7 plies from the root
+        table.store(hash, MATE - 7, 5, 4, Bound::Exact, None);
+        assert_eq!(table.probe(hash, 4).unwrap().score, MATE - 7);
+        // reached after 2 plies it is a mate in 5
+        assert_eq!(table.probe(hash, 2).unwrap().score, MATE - 5);
+        table.store(hash, mated_in(6), 5, 4, Bound::Exact, None);
+        assert_eq!(table.probe(hash, 2).unwrap().score, mated_in(4));
+        table.store(hash, 250, 5, 4, Bound::Exact, None);
+        assert_eq!(table.probe(hash, 2).unwrap().score, 250);
+    }
+
+    #[test]
+    fn test_cutoff() {
+        let entry = Entry {
+            hash: 0,
+            score: 50,
+            depth: 4,
+            bound: Bound::Lower,
+            best_move: None,
+            generation: 0,
+        };
+        assert_eq!(entry.cutoff(4, 0, 40), Some(50));
+        assert_eq!(entry.cutoff(4, 0, 60), None);
+        assert_eq!(entry.cutoff(5, 0, 40), None);
+        let entry = Entry { bound: Bound::Upper, ..entry };
+        assert_eq!(entry.cutoff(2, 60, 100), Some(50));
+        assert_eq!(entry.cutoff(2, 40, 100), None);
+        let entry = Entry { bound: Bound::Exact, ..entry };
+        assert_eq!(entry.cutoff(3, 60, 100), Some(50));
+    }
+
+    #[test]
+    fn test_pack() {
+        let board = Board::default();
+        let hash = board.get_hash();
+        let promotion = ChessMove::new(Square::G7, Square::H8, Some(Piece::Knight));
+        for &(score, depth, bound, best_move) in &[
+            (0, 0, Bound::Exact, None),
+            (-35, 6, Bound::Upper, ChessMove::from_san(&board, "Nf3").ok()),
+            (MATE - 3, 12, Bound::Lower, Some(promotion)),
+            (mated_in(4), 100, Bound::Exact, Some(ChessMove::new(Square::A2, Square::A1, Some(Piece::Queen)))),
+        ] {
+            let entry = unpack(hash, pack(score, depth, bound, best_move, 255)).unwrap();
+            assert_eq!(entry, Entry { hash, score, depth, bound, best_move, generation: 255 });
+        }
+    }
+
+    #[test]
+    fn test_shared() {
+        // threads hammering the same few slots never read an entry another thread didn't write as a whole
+        let table = TranspositionTable::new(0);
+        rayon::scope(|s| {
+            for thread in 0..4 {
+                let table = &table;
+                s.spawn(move |_| {
+                    for i in 0..100_000u64 {
+                        let hash = (i % 7).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                        // every position is always stored with the same score
+                        let score = (hash % 1000) as i32;
+                        table.store(hash, score, (thread + i % 5) as i8, 0, Bound::Exact, None);
+                        if let Some(entry) = table.probe(hash, 0) {
+                            assert_eq!(entry.score, score);
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,113 @@
+use chess::*;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+use super::evaluator::{Classical, Evaluator};
+use super::negamax::*;
+use super::searcher::{SearchConfig, Searcher};
+use super::tt::TranspositionTable;
+
+pub enum SearchMessage {
+    /// sent after every completed iteration
+    Info(SearchResult),
+    /// sent once when the search is over, either because it finished or because it was stopped
+    Done(SearchResult),
+}
+
+/// A search running on its own thread so the caller stays responsive.
+pub struct SearchThread {
+    /// the position being searched
+    pub board: Board,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl SearchThread {
+    /// Starts searching `board`. `report` is called from the search thread with the progress of the search,
+    /// it's usually a closure that forwards the messages into a channel.
+    pub fn spawn<F>(board: Board, config: SearchConfig, table: Arc<TranspositionTable>, report: F) -> Self
+    where
+        F: FnMut(SearchMessage) + Send + 'static,
+    {
+        SearchThread::spawn_with(board, config, Classical::new(config.params), table, report)
+    }
+
+    /// `spawn` with `evaluator` in place of the classical evaluation.
+    pub fn spawn_with<E, F>(board: Board, config: SearchConfig, evaluator: E, table: Arc<TranspositionTable>, mut report: F) -> Self
+    where
+        E: Evaluator + 'static,
+        F: FnMut(SearchMessage) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let search_stop = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            let result = Searcher::with_evaluator(config, &table, evaluator).with_stop(search_stop).search(&board, |info| report(SearchMessage::Info(info.clone())));
+            report(SearchMessage::Done(result));
+        });
+        SearchThread {
+            board,
+            stop,
+            handle,
+        }
+    }
+
+    /// Asks the search to finish as soon as possible. It still reports the best move found so far.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Waits for the search to report its best move.
+    pub fn join(self) {
+        self.handle.join().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::mpsc;
+    use std::time;
+
+    #[test]
+    fn test_stop() {
+        let board = Board::default();
+        let (s, r) = mpsc::channel();
+        let start = time::Instant::now();
+        let table = Arc::new(TranspositionTable::new(1));
+        let config = SearchConfig {
+            threads: 2,
+            ..SearchConfig::full(100)
+        };
+        let search = SearchThread::spawn(board, config, table, move |m| s.send(m).unwrap());
+        // wait for the first iteration so there is a move to play
+        match r.recv().unwrap() {
+            SearchMessage::Info(info) => assert_eq!(info.depth, 2),
+            SearchMessage::Done(_) => panic!("search finished before it was stopped"),
+        }
+        search.stop();
+        search.join();
+        assert!(start.elapsed() < time::Duration::from_secs(5));
+        let best = r.iter().filter_map(|m| match m {
+            SearchMessage::Done(result) => Some(result.best_move),
+            SearchMessage::Info(_) => None,
+        }).next().unwrap().unwrap();
+        assert!(board.legal(best));
+    }
+
+    #[test]
+    fn test_finish() {
+        let board = Board::from_str("2k3r1/5r2/8/8/8/8/8/7K b - - 0 1").unwrap();
+        let (s, r) = mpsc::channel();
+        let table = Arc::new(TranspositionTable::new(1));
+        SearchThread::spawn(board, SearchConfig::full(4), table, move |m| s.send(m).unwrap()).join();
+        let messages = r.iter().collect::<Vec<SearchMessage>>();
+        assert_eq!(messages.len(), 3);
+        match messages[2] {
+            SearchMessage::Done(ref result) => assert_eq!(result.best_move, Some(ChessMove::from_san(&board, "Rh7#").unwrap())),
+            SearchMessage::Info(_) => panic!("expected the best move last"),
+        }
+    }
+}
@@ -4,15 +4,21 @@ use fltk::*;
 use fltk::prelude::*;
 
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, mpsc};
 use std::str::FromStr;
 
-use crate::engine::{negamax::*};
 use crate::engine::*;
+use crate::engine::params::EvalParams;
+use crate::engine::searcher::SearchConfig;
+use crate::engine::tt::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::engine::worker::{SearchMessage, SearchThread};
 use ui_square::{Square, SquareMessage};
 
+mod bench;
 mod engine;
+mod tune;
 mod ui_square;
+mod uci;
 
 lazy_static::lazy_static! {
     pub static ref PIECE_IMAGES: HashMap<String, SvgImage> = get_piece_images();
@@ -20,9 +26,67 @@ lazy_static::lazy_static! {
 }
 
 fn main() { 
-    rayon::ThreadPoolBuilder::new().num_threads(12).build_global().unwrap();  
+    // the search runs on this pool, `--threads <n>` sizes it, by default it gets a thread per cpu
+    let threads = std::env::args().skip_while(|a| a != "--threads").nth(1).and_then(|n| n.parse().ok()).unwrap_or(0);
+    rayon::ThreadPoolBuilder::new().num_threads(threads).build_global().unwrap();
+
+    // `--bench [depth]` searches a fixed set of positions and prints what the search did
+    let args = std::env::args().collect::<Vec<String>>();
+    if let Some(i) = args.iter().position(|a| a == "--bench") {
+        let depth = args.get(i + 1).and_then(|d| d.parse().ok()).unwrap_or(bench::DEFAULT_DEPTH);
+        bench::run(depth, &mut std::io::stdout());
+        return;
+    }
+
+    // `--params <file>` replaces the default evaluation weights with ones saved as TOML or JSON, by the tuner for example
+    let params = match args.iter().position(|a| a == "--params").and_then(|i| args.get(i + 1)) {
+        Some(path) => EvalParams::load(path).unwrap_or_else(|e| {
+            eprintln!("can't load evaluation weights from {}", e);
+            std::process::exit(1);
+        }),
+        None => EvalParams::default(),
+    };
+
+    // `--tune [positions [output]]` tunes the weights on a file of labelled positions, the bundled sample by default,
+    // starting from the loaded ones, and saves the result to `params.toml` or the given file
+    if let Some(i) = args.iter().position(|a| a == "--tune") {
+        let samples = match args.get(i + 1) {
+            Some(path) => std::fs::File::open(path)
+                .map_err(|e| format!("{}: {}", path, e))
+                .and_then(|f| tune::parse_samples(std::io::BufReader::new(f)).map_err(|e| format!("{}: {}", path, e))),
+            None => tune::parse_samples(tune::SAMPLE.as_bytes()),
+        };
+        let samples = samples.unwrap_or_else(|e| {
+            eprintln!("can't read positions from {}", e);
+            std::process::exit(1);
+        });
+        let output = args.get(i + 2).map(String::as_str).unwrap_or("params.toml");
+        let tuned = tune::tune(&samples, &params, usize::MAX, &mut std::io::stdout());
+        if let Err(e) = tuned.save(output) {
+            eprintln!("can't save evaluation weights to {}", e);
+            std::process::exit(1);
+        }
+        println!("saved to {}", output);
+        return;
+    }
+
+    if std::env::args().any(|a| a == "--uci") {
+        let stdin = std::io::stdin();
+        uci::run(stdin.lock(), Arc::new(Mutex::new(std::io::stdout())), params);
+        return;
+    }
     
     let app = app::App::default();
+
+    // the evaluation of the position on the board term by term, opened with the eval button
+    let mut eval_window = window::Window::default()
+        .with_size(460, 260)
+        .with_label("Evaluation");
+    let mut eval_frame = Frame::new(10, 10, 440, 240, "");
+    eval_frame.set_label_font(enums::Font::Courier);
+    eval_frame.set_align(enums::Align::TopLeft | enums::Align::Inside);
+    eval_window.end();
+
     let mut wind = window::Window::default()
         .with_size(640, 480)
         .center_screen()
@@ -46,21 +110,79 @@ fn main() {
     let squares = Square::setup_board_squares(s, square_size, Arc::clone(&drag_frame));
     
 
+    // the evaluation weights the engine plays with, the weights menu changes them
+    let params = Arc::new(Mutex::new(params));
+
     let button_squares_ref = Arc::new(Mutex::new(squares));
     let squares = Arc::clone(&button_squares_ref);
     let squares_app = Arc::clone(&button_squares_ref);
 
+    // the engine searches on its own thread and reports back through this channel
+    let search: Arc<Mutex<Option<SearchThread>>> = Arc::new(Mutex::new(None));
+    let (search_s, search_r) = mpsc::channel::<SearchMessage>();
+    let table = Arc::new(TranspositionTable::new(DEFAULT_HASH_MB));
+
+    // depth, score, principal variation and statistics of the running search
+    let mut info_frame = Frame::new(490, 60, 150, 320, "");
+    info_frame.set_align(enums::Align::TopLeft | enums::Align::Inside | enums::Align::Wrap);
+
     let mut button = Button::new(500,0,20,20, "move");
-    button.set_callback(move |b| {
+    let button_search = Arc::clone(&search);
+    let button_params = Arc::clone(&params);
+    button.set_callback(move |_| {
+        let mut search = button_search.lock().unwrap();
+        if search.is_some() {
+            return;
+        }
         let board = (*BOARD.lock().unwrap()).clone();
-        let m = find_best_move_nega_iterative_transposition_ordering(board, 6);
-        let new_board = board.make_move_new(m);
-        *BOARD.lock().unwrap() = new_board;
-        for square in &mut *button_squares_ref.lock().unwrap() {
-            square.update_image();
+        if MoveGen::new_legal(&board).len() == 0 {
+            return;
+        }
+        let search_s = search_s.clone();
+        let config = SearchConfig { threads: rayon::current_num_threads(), params: *button_params.lock().unwrap(), ..SearchConfig::full(6) };
+        *search = Some(SearchThread::spawn(board, config, Arc::clone(&table), move |m| {
+            search_s.send(m).ok();
+            // wake up the event loop so it picks up the message
+            app::awake();
+        }));
+    });
+
+    let mut stop_button = Button::new(500,30,20,20, "stop");
+    let stop_search = Arc::clone(&search);
+    stop_button.set_callback(move |_| {
+        if let Some(search) = &*stop_search.lock().unwrap() {
+            search.stop();
         }
     });
     
+    let mut eval_button = Button::new(540,0,20,20, "eval");
+    let mut button_eval_window = eval_window.clone();
+    eval_button.set_callback(move |_| button_eval_window.show());
+
+    // loads and saves evaluation weights as TOML or JSON, the next search plays with the loaded ones
+    let mut weights_menu = menu::MenuButton::new(540, 30, 80, 20, "weights");
+    let load_params = Arc::clone(&params);
+    weights_menu.add("load...", enums::Shortcut::None, menu::MenuFlag::Normal, move |_| {
+        if let Some(path) = choose_params_file(dialog::FileDialogType::BrowseFile) {
+            match EvalParams::load(&path) {
+                Ok(loaded) => *load_params.lock().unwrap() = loaded,
+                Err(e) => dialog::alert_default(&format!("can't load evaluation weights from {}", e)),
+            }
+        }
+    });
+    let save_params = Arc::clone(&params);
+    weights_menu.add("save...", enums::Shortcut::None, menu::MenuFlag::Normal, move |_| {
+        if let Some(path) = choose_params_file(dialog::FileDialogType::BrowseSaveFile) {
+            if let Err(e) = save_params.lock().unwrap().save(&path) {
+                dialog::alert_default(&format!("can't save evaluation weights to {}", e));
+            }
+        }
+    });
+    let default_params = Arc::clone(&params);
+    weights_menu.add("default", enums::Shortcut::None, menu::MenuFlag::Normal, move |_| {
+        *default_params.lock().unwrap() = EvalParams::default();
+    });
+
     wind.make_resizable(true);
     wind.end();
     wind.show();
@@ -83,7 +205,36 @@ fn main() {
     });
 
     let mut selected: Option<chess::Square> = None;
+    // the position the evaluation panel shows and the weights it was evaluated with
+    let mut evaluated: Option<(Board, EvalParams)> = None;
     while app.wait() {
+        if eval_window.shown() {
+            let board = *BOARD.lock().unwrap();
+            let params = *params.lock().unwrap();
+            if evaluated != Some((board, params)) {
+                eval_frame.set_label(&eval::explain_eval_params(&board, &params).to_string());
+                evaluated = Some((board, params));
+            }
+        }
+        while let Ok(message) = search_r.try_recv() {
+            match message {
+                SearchMessage::Info(info) => {
+                    info_frame.set_label(&format!("depth {} score {}\npv {}\n\n{}", info.depth, uci::format_score(info.score), uci::format_pv(&info.pv), info.stats));
+                },
+                SearchMessage::Done(result) => {
+                    let finished = search.lock().unwrap().take().unwrap();
+                    let board = *BOARD.lock().unwrap();
+                    // the position may have been changed by hand while the engine was thinking
+                    if let (true, Some(m)) = (board == finished.board, result.best_move) {
+                        *BOARD.lock().unwrap() = board.make_move_new(m);
+                        for square in &mut *button_squares_ref.lock().unwrap() {
+                            square.update_image();
+                        }
+                    }
+                    finished.join();
+                },
+            }
+        }
         match r.recv() {
             None => {
                 println!("{:?}", app::get_mouse())
@@ -173,6 +324,23 @@ fn main() {
     app.run().unwrap();
 }
 
+// asks for a file of evaluation weights, None if the dialog was cancelled
+fn choose_params_file(kind: dialog::FileDialogType) -> Option<std::path::PathBuf> {
+    let mut chooser = dialog::NativeFileChooser::new(kind);
+    chooser.set_title("Evaluation weights");
+    chooser.set_filter("*.{toml,json}");
+    if kind == dialog::FileDialogType::BrowseSaveFile {
+        chooser.set_option(dialog::FileDialogOptions::SaveAsConfirm);
+    }
+    chooser.show();
+    let path = chooser.filename();
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
 fn match_piece<'a>(p: Piece) -> &'a str {
     match p {
         Piece::King => "king",
@@ -0,0 +1,159 @@
+use chess::*;
+
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use crate::engine::eval::evaluate_params;
+use crate::engine::params::EvalParams;
+
+/// Labelled positions that come with the engine, enough to try the tuner out without downloading anything.
+/// Weights worth keeping need a few hundred thousand positions.
+pub const SAMPLE: &str = include_str!("../data/tune_sample.txt");
+
+/// A position together with the result of the game it comes from.
+pub struct Sample {
+    pub board: Board,
+    /// 1 if white won, 0.5 for a draw and 0 if black won
+    pub result: f64,
+}
+
+/// Reads one position per line, a FEN followed by the result of the game in brackets:
+/// `[1-0]`, `[1/2-1/2]` or `[0-1]`. Empty lines and lines starting with `#` are skipped.
+pub fn parse_samples<R: BufRead>(input: R) -> Result<Vec<Sample>, String> {
+    let mut samples = vec![];
+    for (i, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = || format!("line {}: expected a FEN followed by [1-0], [1/2-1/2] or [0-1]", i + 1);
+        let open = line.rfind('[').ok_or_else(error)?;
+        let result = match line[open..].trim_end() {
+            "[1-0]" => 1.0,
+            "[1/2-1/2]" => 0.5,
+            "[0-1]" => 0.0,
+            _ => return Err(error()),
+        };
+        let board = Board::from_str(line[..open].trim()).map_err(|_| error())?;
+        samples.push(Sample { board, result });
+    }
+    Ok(samples)
+}
+
+// the expected result of a game for white given the evaluation, k stretches the curve to fit the evaluation's scale
+fn sigmoid(eval: i32, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval as f64 / 400.0))
+}
+
+/// Mean squared difference between the game results and the results the evaluation with `params` predicts.
+pub fn error(samples: &[Sample], params: &EvalParams, k: f64) -> f64 {
+    let sum = samples.iter().map(|s| (s.result - sigmoid(evaluate_params(&s.board, params), k)).powi(2)).sum::<f64>();
+    sum / samples.len() as f64
+}
+
+/// The `k` the evaluation with `params` predicts the results best with.
+pub fn fit_k(samples: &[Sample], params: &EvalParams) -> f64 {
+    // the error is smooth in k, searching ever finer grids around the best value finds its minimum
+    let mut best = 1.0;
+    let mut step = 0.5;
+    while step > 0.001 {
+        let candidates = (-4..=4).map(|i| best + i as f64 * step).filter(|&k| k > 0.0);
+        best = candidates.map(|k| (k, error(samples, params, k))).fold((best, f64::MAX), |a, b| if b.1 < a.1 { b } else { a }).0;
+        step /= 4.0;
+    }
+    best
+}
+
+// step tried first for every weight, it is halved whenever no weight improves anymore
+const FIRST_STEP: i32 = 8;
+
+/// Texel's tuning method: every weight in turn is raised and lowered by a step, a change is kept whenever it lowers
+/// the error. Once a pass over all weights improves nothing the step is halved, the tuning ends after a pass
+/// with a step of 1 that changed nothing, or after `max_passes` passes. Progress is written to `log`.
+///
+/// The samples are evaluated as they are, without a quiescence search, so they should be quiet positions:
+/// not in check and with no captures that win material. In the middle of an exchange the evaluation says
+/// little about the result and only adds noise.
+pub fn tune<W: Write>(samples: &[Sample], params: &EvalParams, max_passes: usize, log: &mut W) -> EvalParams {
+    let k = fit_k(samples, params);
+    let mut best = *params;
+    let mut best_error = error(samples, &best, k);
+    writeln!(log, "{} positions, k {:.3}, error {:.6}", samples.len(), k, best_error).unwrap();
+    let weights = best.weights_mut().len();
+    let mut step = FIRST_STEP;
+    for pass in 1..=max_passes {
+        let mut improved = false;
+        for i in 0..weights {
+            for &delta in &[step, -step] {
+                let mut candidate = best;
+                *candidate.weight_mut(i) += delta;
+                let candidate_error = error(samples, &candidate, k);
+                if candidate_error < best_error {
+                    best = candidate;
+                    best_error = candidate_error;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        writeln!(log, "pass {} step {} error {:.6}", pass, step, best_error).unwrap();
+        log.flush().unwrap();
+        if !improved {
+            if step == 1 {
+                break;
+            }
+            step /= 2;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::evaluator::{Classical, Evaluator};
+
+    #[test]
+    fn test_parse_samples() {
+        let samples = parse_samples(SAMPLE.as_bytes()).unwrap();
+        assert!(samples.len() > 500);
+        let text = "# comment\n\n8/8/4k3/8/4K3/4P3/8/8 w - - 0 1 [1-0]\n8/8/4k3/8/4K3/8/8/8 b - - 0 1 [1/2-1/2]\n";
+        let samples = parse_samples(text.as_bytes()).unwrap();
+        assert_eq!(samples.iter().map(|s| s.result).collect::<Vec<f64>>(), vec![1.0, 0.5]);
+        assert!(matches!(parse_samples("8/8/4k3/8/4K3/8/8/8 b - - 0 1 [1-1]".as_bytes()), Err(e) if e.starts_with("line 1:")));
+        assert!(parse_samples("8/8/4k3/8/4K3/8/8/8 b - - 0 1".as_bytes()).is_err());
+        assert!(parse_samples("not a position [0-1]".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_sigmoid() {
+        assert_eq!(sigmoid(0, 1.0), 0.5);
+        assert!((sigmoid(400, 1.0) - 10.0 / 11.0).abs() < 1e-9);
+        assert!((sigmoid(-300, 1.2) + sigmoid(300, 1.2) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_tune() {
+        let samples = parse_samples(SAMPLE.as_bytes()).unwrap();
+        let samples = &samples[..150];
+        let params = EvalParams::default();
+        let k = fit_k(samples, &params);
+        let before = error(samples, &params, k);
+        // the fitted k is at least as good as its neighbours
+        assert!(before <= error(samples, &params, k * 1.1) && before <= error(samples, &params, k * 0.9));
+        let mut log = vec![];
+        let tuned = tune(samples, &params, 1, &mut log);
+        assert!(error(samples, &tuned, k) < before);
+        assert_ne!(tuned.pst, params.pst);
+        // the search builds its piece-square scores from the tuned tables too
+        let mut classical = Classical::new(tuned);
+        for sample in samples {
+            let state = classical.state(&sample.board);
+            assert_eq!(classical.evaluate(&sample.board, &state), evaluate_params(&sample.board, &tuned));
+        }
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.starts_with("150 positions, k "));
+        assert!(log.contains("\npass 1 step 8 error "));
+    }
+}
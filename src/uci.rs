@@ -0,0 +1,558 @@
+use chess::*;
+
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use crate::bench;
+use crate::engine::eval::explain_eval_params;
+use crate::engine::evaluator::{Classical, Evaluator};
+use crate::engine::negamax::mate_in_moves;
+use crate::engine::nnue::{Network, Nnue};
+use crate::engine::params::EvalParams;
+use crate::engine::searcher::{SearchConfig, SearchLimits};
+use crate::engine::time::TimeControl;
+use crate::engine::tt::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::engine::worker::{SearchMessage, SearchThread};
+
+// depth used for `go` commands that set neither a depth nor a clock
+const DEFAULT_DEPTH: i8 = 6;
+// deepest iteration of a search that is only limited by time
+const MAX_DEPTH: i8 = 100;
+// most threads the Threads option accepts, a search never uses more than the rayon pool has though
+const MAX_THREADS: usize = 256;
+
+/// Options of a `go` command.
+#[derive(Debug, Default, PartialEq)]
+pub struct GoParams {
+    pub depth: Option<i8>,
+    pub time: TimeControl,
+    pub infinite: bool,
+}
+
+impl GoParams {
+    pub fn parse(args: &[&str]) -> Self {
+        let mut params = GoParams::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match *arg {
+                "depth" => params.depth = args.next().and_then(|v| v.parse().ok()),
+                "movetime" => params.time.movetime = parse_millis(args.next()),
+                "wtime" => params.time.wtime = parse_millis(args.next()),
+                "btime" => params.time.btime = parse_millis(args.next()),
+                "winc" => params.time.winc = parse_millis(args.next()),
+                "binc" => params.time.binc = parse_millis(args.next()),
+                "movestogo" => params.time.movestogo = args.next().and_then(|v| v.parse().ok()),
+                "infinite" => params.infinite = true,
+                _ => (),
+            }
+        }
+        params
+    }
+
+    /// Whether a search with these options only ends once it is stopped.
+    pub fn is_endless(&self, side: Color) -> bool {
+        self.infinite && self.depth.is_none() && self.time.budget(side).is_none()
+    }
+}
+
+// guis send negative times when a player has overstepped the clock
+fn parse_millis(value: Option<&&str>) -> Option<Duration> {
+    value.and_then(|v| v.parse::<i64>().ok()).map(|ms| Duration::from_millis(ms.max(0) as u64))
+}
+
+/// Parses the arguments of a `position` command, e.g. `startpos moves e2e4 e7e5`
+/// or `fen <fen> moves ...`. Returns `None` if the position or one of the moves is invalid.
+pub fn parse_position(args: &[&str]) -> Option<Board> {
+    let moves_index = args.iter().position(|a| *a == "moves").unwrap_or(args.len());
+    let mut board = match args.first() {
+        Some(&"startpos") => Board::default(),
+        Some(&"fen") => Board::from_str(&args[1..moves_index].join(" ")).ok()?,
+        _ => return None,
+    };
+    for m in args.iter().skip(moves_index + 1) {
+        let m = ChessMove::from_str(m).ok()?;
+        if !board.legal(m) {
+            return None;
+        }
+        board = board.make_move_new(m);
+    }
+    Some(board)
+}
+
+/// Parses the arguments of a `setoption` command, `name <name> value <value>`, into the name and the value.
+/// Both may contain spaces, buttons have no value.
+pub fn parse_setoption(args: &[&str]) -> Option<(String, String)> {
+    if args.first() != Some(&"name") {
+        return None;
+    }
+    let value_index = args.iter().position(|a| *a == "value").unwrap_or(args.len());
+    let name = args[1..value_index].join(" ");
+    let value = args.get(value_index + 1..).map(|v| v.join(" ")).unwrap_or_default();
+    Some((name, value))
+}
+
+/// Runs the UCI protocol, reading commands from `input` until `quit` or end of input.
+/// Searches run in the background, so their output is written to `output` from the search thread.
+/// They evaluate positions with `eval_params` until the EvalParams option loads others from a file,
+/// or with a network once the EvalFile option names one.
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: Arc<Mutex<W>>, mut eval_params: EvalParams) {
+    let mut board = Board::default();
+    // kept for the whole game, so every search profits from the ones before it
+    let table = Arc::new(TranspositionTable::new(DEFAULT_HASH_MB));
+    let mut search: Option<Search> = None;
+    let mut threads = 1;
+    let mut quiescence_checks = false;
+    let mut nnue: Option<Nnue> = None;
+    for line in input.lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break,
+        };
+        let tokens = line.split_whitespace().collect::<Vec<&str>>();
+        match tokens.first() {
+            Some(&"uci") => {
+                let mut output = output.lock().unwrap();
+                writeln!(output, "id name AmarChess").unwrap();
+                writeln!(output, "id author Amarantheum").unwrap();
+                writeln!(output, "option name Threads type spin default 1 min 1 max {}", MAX_THREADS).unwrap();
+                writeln!(output, "option name QuiescenceChecks type check default false").unwrap();
+                writeln!(output, "option name EvalParams type string default <empty>").unwrap();
+                writeln!(output, "option name EvalFile type string default <empty>").unwrap();
+                writeln!(output, "uciok").unwrap();
+                output.flush().unwrap();
+            },
+            Some(&"isready") => {
+                let mut output = output.lock().unwrap();
+                writeln!(output, "readyok").unwrap();
+                output.flush().unwrap();
+            },
+            Some(&"ucinewgame") => {
+                stop(search.take());
+                board = Board::default();
+                table.clear();
+            },
+            Some(&"setoption") => {
+                stop(search.take());
+                if let Some((name, value)) = parse_setoption(&tokens[1..]) {
+                    // option names are case insensitive
+                    if name.eq_ignore_ascii_case("threads") {
+                        if let Ok(n) = value.parse::<usize>() {
+                            threads = n.clamp(1, MAX_THREADS);
+                        }
+                    } else if name.eq_ignore_ascii_case("quiescencechecks") {
+                        if let Ok(checks) = value.parse::<bool>() {
+                            quiescence_checks = checks;
+                        }
+                    } else if name.eq_ignore_ascii_case("evalparams") {
+                        // a file of weights saved as TOML or JSON, no file goes back to the built-in weights
+                        match value.as_str() {
+                            "" | "<empty>" => eval_params = EvalParams::default(),
+                            path => match EvalParams::load(path) {
+                                Ok(params) => eval_params = params,
+                                Err(e) => {
+                                    let mut output = output.lock().unwrap();
+                                    writeln!(output, "info string can't load evaluation weights from {}", e).unwrap();
+                                    output.flush().unwrap();
+                                },
+                            },
+                        }
+                    } else if name.eq_ignore_ascii_case("evalfile") {
+                        // a network replaces the classical evaluation, no file brings it back
+                        match value.as_str() {
+                            "" | "<empty>" => nnue = None,
+                            path => match Network::load(path) {
+                                Ok(net) => nnue = Some(Nnue::new(net)),
+                                Err(e) => {
+                                    let mut output = output.lock().unwrap();
+                                    writeln!(output, "info string can't load a network from {}", e).unwrap();
+                                    output.flush().unwrap();
+                                },
+                            },
+                        }
+                    }
+                }
+            },
+            Some(&"position") => {
+                stop(search.take());
+                if let Some(b) = parse_position(&tokens[1..]) {
+                    board = b;
+                }
+            },
+            Some(&"go") => {
+                stop(search.take());
+                let params = GoParams::parse(&tokens[1..]);
+                // the limits are left to go, the rest comes from the options
+                let config = SearchConfig {
+                    threads,
+                    quiescence_checks,
+                    params: eval_params,
+                    ..SearchConfig::full(DEFAULT_DEPTH)
+                };
+                search = Some(go(board, &params, config, nnue.as_ref(), Arc::clone(&table), Arc::clone(&output)));
+            },
+            Some(&"stop") => stop(search.take()),
+            // not part of the protocol, searches the bench positions with the evaluation set by the options
+            // and prints what the search did
+            Some(&"bench") => {
+                stop(search.take());
+                let depth = tokens.get(1).and_then(|d| d.parse().ok()).unwrap_or(bench::DEFAULT_DEPTH);
+                let mut output = output.lock().unwrap();
+                match &nnue {
+                    Some(nnue) => bench::run_with(depth, nnue, &mut *output),
+                    None => bench::run_with(depth, &Classical::new(eval_params), &mut *output),
+                };
+            },
+            // not part of the protocol either, prints the evaluation of the current position term by term
+            Some(&"eval") => {
+                let mut output = output.lock().unwrap();
+                writeln!(output, "{}", explain_eval_params(&board, &eval_params)).unwrap();
+                if let Some(nnue) = &nnue {
+                    let mut nnue = nnue.clone();
+                    let state = nnue.state(&board);
+                    writeln!(output, "network {} from white's point of view", nnue.evaluate(&board, &state)).unwrap();
+                }
+                output.flush().unwrap();
+            },
+            Some(&"quit") => {
+                stop(search.take());
+                return;
+            },
+            _ => (),
+        }
+    }
+    // scripted input ends without a quit, let the last search finish instead of cutting it off,
+    // unless nothing but a stop would ever end it
+    match search {
+        Some(search) if search.hold.is_some() => stop(Some(search)),
+        Some(search) => search.thread.join(),
+        None => (),
+    }
+}
+
+// a search started by `go`
+struct Search {
+    thread: SearchThread,
+    // only set for a search that runs until it is stopped, the best move is held back until this is dropped
+    hold: Option<mpsc::Sender<()>>,
+}
+
+// stops the search and waits for it to print its best move
+fn stop(search: Option<Search>) {
+    if let Some(search) = search {
+        search.thread.stop();
+        drop(search.hold);
+        search.thread.join();
+    }
+}
+
+// searches `board` with `config` within the limits of the go command
+fn go<W: Write + Send + 'static>(board: Board, params: &GoParams, config: SearchConfig, nnue: Option<&Nnue>, table: Arc<TranspositionTable>, output: Arc<Mutex<W>>) -> Search {
+    let budget = params.time.budget(board.side_to_move());
+    let depth = match (params.depth, budget) {
+        (Some(depth), _) => depth,
+        (None, _) if params.infinite => MAX_DEPTH,
+        (None, Some(_)) => MAX_DEPTH,
+        (None, None) => DEFAULT_DEPTH,
+    };
+    let depth = depth.max(1);
+    // threads beyond the size of the pool would only wait for the others to finish
+    let config = SearchConfig {
+        limits: SearchLimits { depth, budget },
+        threads: config.threads.min(rayon::current_num_threads()),
+        ..config
+    };
+    // the protocol doesn't allow a bestmove before the stop of an infinite search, even if the search is over sooner
+    let (hold, release) = if params.is_endless(board.side_to_move()) {
+        let (hold, release) = mpsc::channel::<()>();
+        (Some(hold), Some(release))
+    } else {
+        (None, None)
+    };
+    let report = move |message| {
+        if let (SearchMessage::Done(_), Some(release)) = (&message, &release) {
+            // nothing is ever sent, this returns once the sender is dropped
+            release.recv().ok();
+        }
+        let mut output = output.lock().unwrap();
+        match message {
+            SearchMessage::Info(info) => writeln!(output, "info depth {} score {} nodes {} nps {} time {} pv {}", info.depth, format_score(info.score), info.stats.nodes, info.stats.nps(), info.stats.time.as_millis(), format_pv(&info.pv)).unwrap(),
+            SearchMessage::Done(result) => writeln!(output, "bestmove {}", format_best_move(result.best_move)).unwrap(),
+        }
+        output.flush().unwrap();
+    };
+    let thread = match nnue {
+        Some(nnue) => SearchThread::spawn_with(board, config, nnue.clone(), table, report),
+        None => SearchThread::spawn(board, config, table, report),
+    };
+    Search {
+        thread,
+        hold,
+    }
+}
+
+/// Formats a score the way `info` lines report it, `cp <centipawns>` or `mate <moves>`.
+pub fn format_score(score: i32) -> String {
+    match mate_in_moves(score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", score),
+    }
+}
+
+/// Formats the move a search picked, `0000` if there was none to pick.
+pub fn format_best_move(m: Option<ChessMove>) -> String {
+    m.map(|m| m.to_string()).unwrap_or_else(|| "0000".to_string())
+}
+
+pub fn format_pv(pv: &[ChessMove]) -> String {
+    pv.iter().map(|m| m.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::negamax::MATE;
+
+    fn run_script(script: &str) -> String {
+        let output = Arc::new(Mutex::new(vec![]));
+        run(script.as_bytes(), Arc::clone(&output), EvalParams::default());
+        let output = output.lock().unwrap();
+        String::from_utf8(output.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_handshake() {
+        let out = run_script("uci\nisready\nquit\n");
+        assert_eq!(out, "id name AmarChess\nid author Amarantheum\noption name Threads type spin default 1 min 1 max 256\noption name QuiescenceChecks type check default false\noption name EvalParams type string default <empty>\noption name EvalFile type string default <empty>\nuciok\nreadyok\n");
+    }
+
+    #[test]
+    fn test_parse_setoption() {
+        assert_eq!(parse_setoption(&["name", "Threads", "value", "4"]), Some(("Threads".to_string(), "4".to_string())));
+        assert_eq!(parse_setoption(&["name", "Clear", "Hash"]), Some(("Clear Hash".to_string(), "".to_string())));
+        assert_eq!(parse_setoption(&["Threads", "4"]), None);
+    }
+
+    #[test]
+    fn test_threads() {
+        let out = run_script("setoption name Threads value 4\nposition fen 2k3r1/5r2/8/8/8/8/8/7K b - - 0 1\ngo depth 4\n");
+        assert!(out.contains(" score mate 1 "));
+        assert!(out.ends_with("bestmove f7h7\n"));
+    }
+
+    #[test]
+    fn test_quiescence_checks() {
+        // the only move walks into a mate, at depth 1 only a quiescence search that tries checks finds it
+        let position = "position fen 7k/8/8/8/8/8/5R2/1K4R1 b - - 0 1\n";
+        let out = run_script(&format!("{}go depth 1\n", position));
+        assert!(!out.contains(" score mate "));
+        let out = run_script(&format!("setoption name QuiescenceChecks value true\n{}go depth 1\n", position));
+        assert!(out.contains(" score mate "));
+        let out = run_script(&format!("setoption name QuiescenceChecks value true\nsetoption name QuiescenceChecks value false\n{}go depth 1\n", position));
+        assert!(!out.contains(" score mate "));
+    }
+
+    #[test]
+    fn test_parse_position() {
+        let board = parse_position(&["startpos", "moves", "e2e4", "e7e5"]).unwrap();
+        assert_eq!(board, Board::from_str("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2").unwrap());
+        let board = parse_position(&["fen", "7k/8/8/8/8/8/5R2/1K4R1", "w", "-", "-", "0", "1", "moves", "f2h2"]).unwrap();
+        assert_eq!(board, Board::from_str("7k/8/8/8/8/8/7R/1K4R1 b - - 1 1").unwrap());
+        assert!(parse_position(&["startpos", "moves", "e2e5"]).is_none());
+    }
+
+    #[test]
+    fn test_parse_go() {
+        let params = GoParams::parse(&["wtime", "1000", "btime", "2000", "winc", "10", "binc", "20", "movestogo", "30"]);
+        assert_eq!(params, GoParams {
+            time: TimeControl {
+                wtime: Some(Duration::from_millis(1000)),
+                btime: Some(Duration::from_millis(2000)),
+                winc: Some(Duration::from_millis(10)),
+                binc: Some(Duration::from_millis(20)),
+                movestogo: Some(30),
+                movetime: None,
+            },
+            ..GoParams::default()
+        });
+        let params = GoParams::parse(&["depth", "8", "movetime", "500"]);
+        assert_eq!(params.depth, Some(8));
+        assert_eq!(params.time, TimeControl::movetime(Duration::from_millis(500)));
+        assert_eq!(GoParams::parse(&["wtime", "-20"]).time.wtime, Some(Duration::from_millis(0)));
+    }
+
+    #[test]
+    fn test_info_pv() {
+        let out = run_script("position startpos\ngo depth 4\n");
+        let line = out.lines().find(|l| l.starts_with("info depth 4 ")).unwrap();
+        let pv = &line[line.find(" pv ").unwrap() + 4..];
+        assert!(pv.split(' ').count() > 1);
+        let mut board = Board::default();
+        for m in pv.split(' ') {
+            let m = ChessMove::from_str(m).unwrap();
+            assert!(board.legal(m));
+            board = board.make_move_new(m);
+        }
+        assert!(out.ends_with(&format!("bestmove {}\n", pv.split(' ').next().unwrap())));
+    }
+
+    #[test]
+    fn test_go_movetime() {
+        let start = std::time::Instant::now();
+        let out = run_script("position startpos moves e2e4\ngo movetime 300\n");
+        assert!(start.elapsed() < Duration::from_millis(600));
+        assert!(out.contains("info depth 2 "));
+        assert!(out.contains("bestmove "));
+    }
+
+    // runs `go infinite` on `position` and sends stop after a while, returns the output
+    fn run_infinite(position: &str) -> String {
+        let (s, r) = mpsc::channel();
+        let output = Arc::new(Mutex::new(vec![]));
+        let thread_output = Arc::clone(&output);
+        // keeps the input open until the test decides to send stop
+        let input = std::io::BufReader::new(ChannelReader(r, vec![]));
+        let uci = std::thread::spawn(move || run(input, thread_output, EvalParams::default()));
+        s.send(format!("position {}\ngo infinite\n", position)).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(!String::from_utf8(output.lock().unwrap().clone()).unwrap().contains("bestmove"), "{}", position);
+        s.send("stop\n".to_string()).unwrap();
+        drop(s);
+        uci.join().unwrap();
+        let out = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert_eq!(out.matches("bestmove ").count(), 1);
+        out
+    }
+
+    #[test]
+    fn test_go_infinite_stop() {
+        assert!(run_infinite("startpos").contains("info depth 2 "));
+        // a mate in one is searched to the last depth long before the stop, no moves at all at once
+        assert!(run_infinite("fen 2k3r1/5r2/8/8/8/8/8/7K b - - 0 1").ends_with("bestmove f7h7\n"));
+        assert_eq!(run_infinite("fen 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), "bestmove 0000\n");
+    }
+
+    #[test]
+    fn test_go_infinite_eof() {
+        // the input ends without a stop, the search must not run forever
+        let out = run_script("position startpos\ngo infinite\n");
+        assert_eq!(out.matches("bestmove ").count(), 1);
+        assert!(!GoParams::parse(&["infinite", "depth", "4"]).is_endless(Color::White));
+        assert!(!GoParams::parse(&["infinite", "movetime", "100"]).is_endless(Color::White));
+    }
+
+    struct ChannelReader(mpsc::Receiver<String>, Vec<u8>);
+
+    impl std::io::Read for ChannelReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.1.is_empty() {
+                match self.0.recv() {
+                    Ok(s) => self.1 = s.into_bytes(),
+                    Err(_) => return Ok(0),
+                }
+            }
+            let n = buf.len().min(self.1.len());
+            buf[..n].copy_from_slice(&self.1[..n]);
+            self.1.drain(..n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_info_stats() {
+        let out = run_script("position startpos\ngo depth 4\n");
+        let line = out.lines().find(|l| l.starts_with("info depth 4 ")).unwrap();
+        assert!(line.contains(" nodes "));
+        assert!(line.contains(" nps "));
+        assert!(line.contains(" time "));
+    }
+
+    #[test]
+    fn test_bench() {
+        let out = run_script("bench 2\nisready\n");
+        assert!(out.contains("\ntotal\nnodes "));
+        assert!(out.ends_with("readyok\n"));
+    }
+
+    #[test]
+    fn test_eval() {
+        let out = run_script("position startpos moves e2e4\neval\n");
+        assert!(out.contains("\nmobility "));
+        let board = parse_position(&["startpos", "moves", "e2e4"]).unwrap();
+        assert!(out.ends_with(&format!("total {} from white's point of view\n", crate::engine::eval::evaluate(&board))));
+    }
+
+    #[test]
+    fn test_eval_params() {
+        let path = std::env::temp_dir().join("amar_chess_test_uci_params.json");
+        let mut params = EvalParams::default();
+        params.material[0] = crate::engine::eval::score(150, 150);
+        params.save(&path).unwrap();
+        let board = parse_position(&["startpos", "moves", "e2e4", "d7d5", "e4d5"]).unwrap();
+        let total = |params: &EvalParams| format!("total {} from white's point of view\n", crate::engine::eval::evaluate_params(&board, params));
+        let position = "position startpos moves e2e4 d7d5 e4d5\n";
+        let out = run_script(&format!("setoption name EvalParams value {}\n{}eval\n", path.display(), position));
+        assert!(out.ends_with(&total(&params)));
+        let out = run_script(&format!("setoption name EvalParams value {}\nsetoption name EvalParams value <empty>\n{}eval\n", path.display(), position));
+        assert!(out.ends_with(&total(&EvalParams::default())));
+        std::fs::remove_file(&path).unwrap();
+        // a missing file keeps the weights there were
+        let out = run_script(&format!("setoption name EvalParams value {}\n{}eval\n", path.display(), position));
+        assert!(out.starts_with("info string can't load evaluation weights from "));
+        assert!(out.ends_with(&total(&EvalParams::default())));
+    }
+
+    #[test]
+    fn test_eval_file() {
+        let path = std::env::temp_dir().join("amar_chess_test_uci.nnue");
+        std::fs::write(&path, Network::tiny().to_bytes()).unwrap();
+        let board = parse_position(&["startpos", "moves", "e2e4"]).unwrap();
+        let mut nnue = Nnue::new(Network::tiny());
+        let state = nnue.state(&board);
+        let out = run_script(&format!("setoption name EvalFile value {}\nposition startpos moves e2e4\neval\n", path.display()));
+        assert!(out.ends_with(&format!("network {} from white's point of view\n", nnue.evaluate(&board, &state))));
+        let out = run_script(&format!("setoption name EvalFile value {}\nposition fen 2k3r1/5r2/8/8/8/8/8/7K b - - 0 1\ngo depth 4\n", path.display()));
+        assert!(out.ends_with("bestmove f7h7\n"));
+        let out = run_script(&format!("setoption name EvalFile value {}\nsetoption name EvalFile value <empty>\neval\n", path.display()));
+        assert!(!out.contains("network"));
+        std::fs::remove_file(&path).unwrap();
+        let out = run_script(&format!("setoption name EvalFile value {}\neval\n", path.display()));
+        assert!(out.starts_with("info string can't load a network from "));
+        assert!(!out.contains("\nnetwork"));
+    }
+
+    #[test]
+    fn test_go_mate_in_1() {
+        let out = run_script("position fen 2k3r1/5r2/8/8/8/8/8/7K b - - 0 1\ngo depth 4\n");
+        assert!(out.contains("info depth 2 "));
+        assert!(out.contains("info depth 4 "));
+        assert!(out.contains(" score mate 1 "));
+        assert!(out.contains(" pv f7h7\n"));
+        assert!(out.ends_with("bestmove f7h7\n"));
+    }
+
+    #[test]
+    fn test_go_odd_depth() {
+        let out = run_script("position startpos\ngo depth 3\n");
+        assert!(out.contains("info depth 2 "));
+        assert!(out.contains("info depth 3 "));
+        assert!(!out.contains("info depth 4 "));
+        let out = run_script("position startpos\ngo depth 1\n");
+        assert!(out.starts_with("info depth 1 "));
+    }
+
+    #[test]
+    fn test_format_score() {
+        assert_eq!(format_score(35), "cp 35");
+        assert_eq!(format_score(-120), "cp -120");
+        assert_eq!(format_score(MATE - 3), "mate 2");
+        assert_eq!(format_score(-(MATE - 4)), "mate -2");
+    }
+
+    #[test]
+    fn test_go_no_moves() {
+        let out = run_script("position fen 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1\ngo depth 2\n");
+        assert_eq!(out, "bestmove 0000\n");
+    }
+}